@@ -2,7 +2,6 @@ extern crate ffms2;
 extern crate structopt;
 
 use std::fs::File;
-use std::io::Write;
 use std::path::{Path, PathBuf};
 use structopt::StructOpt;
 
@@ -23,9 +22,16 @@ struct CliArgs {
     /// Force overwriting of existing index file, if any
     #[structopt(short = "f", long = "force")]
     force: bool,
-    /// Set FFmpeg verbosity level
-    #[structopt(short = "v", long = "verbose", default_value = "0")]
-    verbose: usize,
+    /// Set FFmpeg verbosity level, from 0 (quiet) to 4 (debug),
+    /// or by name (quiet, panic, fatal, error, warning, info, verbose,
+    /// debug, trace)
+    #[structopt(
+        short = "v",
+        long = "verbose",
+        default_value = "0",
+        parse(try_from_str = parse_log_level)
+    )]
+    verbose: LogLevels,
     /// Disable progress reporting
     #[structopt(short = "p", long = "progress")]
     progress: bool,
@@ -39,9 +45,15 @@ struct CliArgs {
     /// (-1 means index all tracks, 0 means index none)
     #[structopt(short = "t", long = "index", default_value = "0")]
     index_mask: i64,
-    /// Set audio decoding error handling
-    #[structopt(short = "s", long = "audio-decoding", default_value = "0")]
-    ignore_errors: usize,
+    /// Set audio decoding error handling, from 0 (ignore) to 3 (abort),
+    /// or by name (abort, clear_track, stop_track, ignore)
+    #[structopt(
+        short = "s",
+        long = "audio-decoding",
+        default_value = "0",
+        parse(try_from_str = parse_error_handling)
+    )]
+    ignore_errors: IndexErrorHandling,
     /// The file to be indexed
     #[structopt(parse(from_os_str))]
     input_file: PathBuf,
@@ -51,6 +63,29 @@ struct CliArgs {
     output_file: Option<PathBuf>,
 }
 
+// Numeric levels keep the meaning they had before names were accepted
+fn parse_log_level(level: &str) -> Result<LogLevels, ParseEnumError> {
+    match level.parse::<usize>() {
+        Ok(0) => Ok(LogLevels::LOG_QUIET),
+        Ok(1) => Ok(LogLevels::LOG_WARNING),
+        Ok(2) => Ok(LogLevels::LOG_INFO),
+        Ok(3) => Ok(LogLevels::LOG_VERBOSE),
+        Ok(_) => Ok(LogLevels::LOG_DEBUG),
+        Err(_) => level.parse(),
+    }
+}
+
+fn parse_error_handling(mode: &str) -> Result<IndexErrorHandling, String> {
+    match mode.parse::<usize>() {
+        Ok(0) => Ok(IndexErrorHandling::IEH_IGNORE),
+        Ok(1) => Ok(IndexErrorHandling::IEH_STOP_TRACK),
+        Ok(2) => Ok(IndexErrorHandling::IEH_CLEAR_TRACK),
+        Ok(3) => Ok(IndexErrorHandling::IEH_ABORT),
+        Ok(_) => Err("invalid audio decoding error handling mode".into()),
+        Err(_) => mode.parse().map_err(|err: ParseEnumError| err.to_string()),
+    }
+}
+
 fn update_progress(
     current: usize,
     total: usize,
@@ -76,7 +111,7 @@ fn dump_filename(
     cache_file: &Path,
     suffix: &str,
 ) -> PathBuf {
-    if track.NumFrames() == 0 {
+    if track.frame_count() == 0 {
         return PathBuf::new();
     }
 
@@ -111,20 +146,20 @@ fn do_indexing(
     }
 
     if args.index_mask == -1 {
-        indexer.TrackTypeIndexSettings(TrackType::TYPE_AUDIO, 1);
+        indexer.enable_track_type(TrackType::TYPE_AUDIO).unwrap();
     }
 
-    for i in 0..64 {
+    for i in 0..indexer.NumTracksI().min(64) {
         if ((args.index_mask >> i) & 1) != 0 {
-            indexer.TrackIndexSettings(i, 1);
+            indexer.enable_track(i).unwrap();
         }
     }
 
-    let index = indexer.DoIndexing2(ignore_errors).unwrap();
+    let index = indexer.do_indexing(ignore_errors).unwrap();
 
     if args.timecodes {
         print_progress!(args.progress, "Writing timecodes...");
-        let num_tracks = index.NumTracks();
+        let num_tracks = index.tracks_count();
         for t in 0..num_tracks {
             let track = Track::TrackFromIndex(&index, t);
             let filename = dump_filename(&track, t, &cache_file, ".tc.txt");
@@ -142,19 +177,13 @@ fn do_indexing(
 
     if args.keyframes {
         print_progress!(args.progress, "Writing keyframes...");
-        let num_tracks = index.NumTracks();
+        let num_tracks = index.tracks_count();
         for t in 0..num_tracks {
             let track = Track::TrackFromIndex(&index, t);
             let filename = dump_filename(&track, t, &cache_file, ".kf.txt");
             if !filename.to_str().unwrap().is_empty() {
                 let mut file = File::create(filename)?;
-                write!(file, "# keyframe format v1\nfps 0\n")?;
-                let frame_count = track.NumFrames();
-                for f in 0..frame_count {
-                    if track.FrameInfo(f).KeyFrame() != 0 {
-                        writeln!(file, "{}", f)?;
-                    }
-                }
+                track.write_keyframes_to(&mut file)?;
             }
         }
         print_progress!(args.progress, "Done.");
@@ -162,7 +191,7 @@ fn do_indexing(
 
     print_progress!(args.progress, "Writing index...");
 
-    index.WriteIndex(&cache_file).unwrap();
+    index.write_to_file(&cache_file).unwrap();
 
     print_progress!(args.progress, "Done.");
 
@@ -172,10 +201,6 @@ fn do_indexing(
 fn main() {
     let args = CliArgs::from_args();
 
-    if args.ignore_errors > 3 {
-        panic!("Error: invalid audio decoding error handling mode");
-    }
-
     let cache_file = if let Some(out) = &args.output_file {
         out.to_path_buf()
     } else {
@@ -192,22 +217,7 @@ fn main() {
 
     FFMS2::Init();
 
-    let level = match args.verbose {
-        0 => LogLevels::LOG_QUIET,
-        1 => LogLevels::LOG_WARNING,
-        2 => LogLevels::LOG_INFO,
-        3 => LogLevels::LOG_VERBOSE,
-        _ => LogLevels::LOG_DEBUG,
-    };
-
-    Log::SetLogLevel(level);
-
-    let ignore_errors = match args.ignore_errors {
-        0 => IndexErrorHandling::IEH_IGNORE,
-        1 => IndexErrorHandling::IEH_STOP_TRACK,
-        2 => IndexErrorHandling::IEH_CLEAR_TRACK,
-        _ => IndexErrorHandling::IEH_ABORT,
-    };
+    Log::SetLogLevel(args.verbose);
 
-    do_indexing(&args, &cache_file, ignore_errors).unwrap();
+    do_indexing(&args, &cache_file, args.ignore_errors).unwrap();
 }
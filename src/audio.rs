@@ -6,11 +6,14 @@ use ffms2_sys::*;
 
 use std::ffi::c_void;
 use std::ffi::CString;
+use std::marker::PhantomData;
 use std::mem;
 use std::path::Path;
 
-simple_enum!(
+create_enum!(
     AudioChannel,
+    FFMS_AudioChannel,
+    audio_channel,
     (
         CH_FRONT_LEFT,
         CH_FRONT_RIGHT,
@@ -35,6 +38,84 @@ simple_enum!(
     )
 );
 
+impl AudioChannel {
+    const ALL: [AudioChannel; 20] = [
+        AudioChannel::CH_FRONT_LEFT,
+        AudioChannel::CH_FRONT_RIGHT,
+        AudioChannel::CH_FRONT_CENTER,
+        AudioChannel::CH_LOW_FREQUENCY,
+        AudioChannel::CH_BACK_LEFT,
+        AudioChannel::CH_BACK_RIGHT,
+        AudioChannel::CH_FRONT_LEFT_OF_CENTER,
+        AudioChannel::CH_FRONT_RIGHT_OF_CENTER,
+        AudioChannel::CH_BACK_CENTER,
+        AudioChannel::CH_SIDE_LEFT,
+        AudioChannel::CH_SIDE_RIGHT,
+        AudioChannel::CH_TOP_CENTER,
+        AudioChannel::CH_TOP_FRONT_LEFT,
+        AudioChannel::CH_TOP_FRONT_CENTER,
+        AudioChannel::CH_TOP_FRONT_RIGHT,
+        AudioChannel::CH_TOP_BACK_LEFT,
+        AudioChannel::CH_TOP_BACK_CENTER,
+        AudioChannel::CH_TOP_BACK_RIGHT,
+        AudioChannel::CH_STEREO_LEFT,
+        AudioChannel::CH_STEREO_RIGHT,
+    ];
+
+    /// Channels present in a `ChannelLayout` bit mask, in the order they
+    /// are interleaved in decoded audio. `None` when no known channel is
+    /// set.
+    pub fn channel_map(channel_layout: i64) -> Option<Vec<AudioChannel>> {
+        let channels_map: Vec<AudioChannel> = AudioChannel::ALL
+            .iter()
+            .copied()
+            .filter(|channel| {
+                channel_layout & channel.to_audio_channel() as i64 != 0
+            })
+            .collect();
+
+        (!channels_map.is_empty()).then_some(channels_map)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ChannelLayout(pub i64);
+
+impl ChannelLayout {
+    /// Conventional name of the layout, as printed by FFmpeg, or `"custom"`
+    /// for other channel sets.
+    pub fn name(&self) -> &'static str {
+        match self.0 {
+            0x4 => "mono",
+            0x3 => "stereo",
+            0xB => "2.1",
+            0x7 => "3.0",
+            0x33 => "quad",
+            0x37 => "5.0",
+            0x607 => "5.0(side)",
+            0x3F => "5.1",
+            0x60F => "5.1(side)",
+            0x13F => "6.1",
+            0x63F => "7.1",
+            0xFF => "7.1(wide)",
+            0x6000_0000 => "downmix",
+            _ => "custom",
+        }
+    }
+
+    pub fn channels(&self) -> Option<Vec<AudioChannel>> {
+        AudioChannel::channel_map(self.0)
+    }
+
+    pub fn from_channels(channels: &[AudioChannel]) -> Self {
+        ChannelLayout(
+            channels.iter().fold(0, |mask, channel| {
+                mask | channel.to_audio_channel() as i64
+            }),
+        )
+    }
+}
+
 simple_enum!(
     AudioDelay,
     (DELAY_NO_SHIFT, DELAY_TIME_ZERO, DELAY_FIRST_VIDEO_TRACK)
@@ -71,6 +152,293 @@ create_struct!(
     (0, 0, 0, 0, 0, 0, 0.0, 0.0, 0.0)
 );
 
+serde_struct!(
+    AudioProperties,
+    audio_properties,
+    (
+        SampleFormat,
+        SampleRate,
+        BitsPerSample,
+        Channels,
+        ChannelLayout,
+        NumSamples,
+        FirstTime,
+        LastTime,
+        LastEndTime,
+    )
+);
+
+impl AudioProperties {
+    pub fn channel_layout(&self) -> ChannelLayout {
+        ChannelLayout(self.audio_properties.ChannelLayout)
+    }
+
+    pub fn has_start_delay(&self) -> bool {
+        self.audio_properties.FirstTime != 0.0
+    }
+
+    pub fn start_delay_seconds(&self) -> f64 {
+        self.audio_properties.FirstTime
+    }
+}
+
+mod sealed {
+    pub trait Sealed {}
+
+    impl Sealed for u8 {}
+    impl Sealed for i16 {}
+    impl Sealed for i32 {}
+    impl Sealed for f32 {}
+    impl Sealed for f64 {}
+}
+
+/// Types FFMS2 decodes samples into. Sealed, since decoded bytes are
+/// written straight into buffers of these types.
+pub trait Sample: Copy + sealed::Sealed {
+    const FORMAT: SampleFormat;
+}
+
+impl Sample for u8 {
+    const FORMAT: SampleFormat = SampleFormat::FMT_U8;
+}
+
+impl Sample for i16 {
+    const FORMAT: SampleFormat = SampleFormat::FMT_S16;
+}
+
+impl Sample for i32 {
+    const FORMAT: SampleFormat = SampleFormat::FMT_S32;
+}
+
+impl Sample for f32 {
+    const FORMAT: SampleFormat = SampleFormat::FMT_FLT;
+}
+
+impl Sample for f64 {
+    const FORMAT: SampleFormat = SampleFormat::FMT_DBL;
+}
+
+pub struct DecodedAudio {
+    pub samples: Vec<u8>,
+    pub format: SampleFormat,
+    pub channels: usize,
+    pub sample_rate: usize,
+}
+
+impl DecodedAudio {
+    fn typed<T: Sample, const N: usize>(
+        &self,
+        from_ne_bytes: fn([u8; N]) -> T,
+    ) -> Option<Vec<T>> {
+        (self.format == T::FORMAT).then(|| {
+            self.samples
+                .chunks_exact(N)
+                .map(|bytes| from_ne_bytes(bytes.try_into().unwrap()))
+                .collect()
+        })
+    }
+
+    pub fn as_u8(&self) -> Option<Vec<u8>> {
+        self.typed(u8::from_ne_bytes)
+    }
+
+    pub fn as_i16(&self) -> Option<Vec<i16>> {
+        self.typed(i16::from_ne_bytes)
+    }
+
+    pub fn as_i32(&self) -> Option<Vec<i32>> {
+        self.typed(i32::from_ne_bytes)
+    }
+
+    pub fn as_f32(&self) -> Option<Vec<f32>> {
+        self.typed(f32::from_ne_bytes)
+    }
+
+    pub fn as_f64(&self) -> Option<Vec<f64>> {
+        self.typed(f64::from_ne_bytes)
+    }
+}
+
+/// Decoded samples in the sample format of the stream.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Samples {
+    U8(Vec<u8>),
+    S16(Vec<i16>),
+    S32(Vec<i32>),
+    Flt(Vec<f32>),
+    Dbl(Vec<f64>),
+}
+
+fn check_sample_format<T: Sample>(format: i32) -> Result<(), Error> {
+    let format = SampleFormat::from_i32(format);
+    if format == T::FORMAT {
+        Ok(())
+    } else {
+        Err(Error::new(
+            Errors::ERROR_DECODING,
+            Errors::ERROR_INVALID_ARGUMENT,
+            &format!(
+                "Requested {:?} samples from a {:?} stream",
+                T::FORMAT,
+                format
+            ),
+        ))
+    }
+}
+
+// Sample format the output has to be converted to for reading `T` samples
+fn conversion_target<T: Sample>(format: i32) -> Option<SampleFormat> {
+    check_sample_format::<T>(format).err().map(|_| T::FORMAT)
+}
+
+// NaN samples, which no clamping can bring in range, are silenced
+fn clip_samples(samples: &mut [f32]) -> bool {
+    let mut clipped = false;
+    for sample in samples.iter_mut() {
+        if sample.is_nan() {
+            clipped = true;
+            *sample = 0.0;
+        } else if sample.abs() > 1.0 {
+            clipped = true;
+            *sample = sample.clamp(-1.0, 1.0);
+        }
+    }
+    clipped
+}
+
+fn check_sample_range(
+    start: usize,
+    count: usize,
+    samples_count: i64,
+) -> Result<(), Error> {
+    match start.checked_add(count) {
+        Some(end) if end as u64 <= samples_count.max(0) as u64 => Ok(()),
+        _ => Err(Error::new(
+            Errors::ERROR_DECODING,
+            Errors::ERROR_INVALID_ARGUMENT,
+            "Requesting samples beyond the stream end",
+        )),
+    }
+}
+
+// Size of the buffer `FFMS_GetAudio` writes `count` samples from `start` to
+fn block_bytes(
+    start: usize,
+    count: usize,
+    audio_prop: &FFMS_AudioProperties,
+) -> Result<usize, Error> {
+    check_sample_range(start, count, audio_prop.NumSamples)?;
+    let format = SampleFormat::from_i32(audio_prop.SampleFormat);
+    usize::try_from(audio_prop.Channels)
+        .ok()
+        .and_then(|channels| count.checked_mul(channels))
+        .and_then(|samples| samples.checked_mul(format.bytes_per_sample()))
+        .ok_or_else(|| {
+            Error::new(
+                Errors::ERROR_DECODING,
+                Errors::ERROR_INVALID_ARGUMENT,
+                &format!("{} samples do not fit in memory", count),
+            )
+        })
+}
+
+// Number of `T` values `FFMS_GetAudio` writes for `count` samples, only
+// once `T` is known to match the sample format of the stream
+fn sample_buffer_len<T: Sample>(
+    start: usize,
+    count: usize,
+    audio_prop: &FFMS_AudioProperties,
+) -> Result<usize, Error> {
+    check_sample_format::<T>(audio_prop.SampleFormat)?;
+    Ok(block_bytes(start, count, audio_prop)? / mem::size_of::<T>())
+}
+
+// Tracks which were not indexed have no packets
+fn samples_per_packet(samples: i64, packets: i32) -> Option<usize> {
+    let samples = usize::try_from(samples).ok()?;
+    samples.checked_div(usize::try_from(packets).ok()?)
+}
+
+fn prepare_buffer<T>(buf: &mut Vec<T>, len: usize) {
+    buf.clear();
+    buf.reserve(len);
+}
+
+fn for_each_block<D, P>(
+    total: usize,
+    block: usize,
+    mut decode: D,
+    mut progress: P,
+) -> Result<(), Error>
+where
+    D: FnMut(usize, usize) -> Result<(), Error>,
+    P: FnMut(usize, usize),
+{
+    let mut done = 0;
+    while done < total {
+        let count = block.max(1).min(total - done);
+        decode(done, count)?;
+        done += count;
+        progress(done, total);
+    }
+    Ok(())
+}
+
+fn check_channels(
+    channels: &[usize],
+    channels_count: usize,
+) -> Result<(), Error> {
+    match channels.iter().find(|c| **c >= channels_count) {
+        Some(channel) => Err(Error::new(
+            Errors::ERROR_DECODING,
+            Errors::ERROR_INVALID_ARGUMENT,
+            &format!(
+                "Channel {} does not exist, the track has {} channels",
+                channel, channels_count
+            ),
+        )),
+        None => Ok(()),
+    }
+}
+
+fn deinterleave<T: Copy>(
+    samples: &[T],
+    channels_count: usize,
+    channels: &[usize],
+) -> Vec<Vec<T>> {
+    channels
+        .iter()
+        .map(|channel| {
+            samples
+                .chunks_exact(channels_count)
+                .map(|frame| frame[*channel])
+                .collect()
+        })
+        .collect()
+}
+
+fn fixed_frame_starts(
+    total: usize,
+    frame_len: usize,
+    pad: bool,
+) -> impl Iterator<Item = usize> {
+    let frames = if pad {
+        total.div_ceil(frame_len)
+    } else {
+        total / frame_len
+    };
+    (0..frames).map(move |i| i * frame_len)
+}
+
+// Start and length of the chunk following `next`, the last one is shorter
+fn next_chunk(
+    next: usize,
+    total: usize,
+    chunk: usize,
+) -> Option<(usize, usize)> {
+    (next < total).then(|| (next, chunk.min(total - next)))
+}
+
 pub struct AudioSource {
     audio_source: *mut FFMS_AudioSource,
 }
@@ -112,33 +480,110 @@ impl AudioSource {
         }
     }
 
-    pub fn GetAudio<T>(
+    pub fn GetAudio<T: Sample>(
         &self,
         Start: usize,
         Count: usize,
     ) -> Result<Vec<T>, Error> {
+        let mut Buf = Vec::new();
+        self.audio_into(Start, Count, &mut Buf)?;
+        Ok(Buf)
+    }
+
+    /// Decodes `count` samples into `buf`, replacing its content. The
+    /// capacity of `buf` is reused and only grows when it is too small, so
+    /// streaming decoders can keep a single buffer around. Fails when `T`
+    /// does not match the sample format of the stream.
+    pub fn audio_into<T: Sample>(
+        &self,
+        start: usize,
+        count: usize,
+        buf: &mut Vec<T>,
+    ) -> Result<(), Error> {
         let mut error: Error = Default::default();
         let audio_prop = self.GetAudioProperties();
-        let num_samples = audio_prop.NumSamples;
+        let num_elements = sample_buffer_len::<T>(
+            start,
+            count,
+            &audio_prop.audio_properties,
+        )?;
+        prepare_buffer(buf, num_elements);
 
-        if Start as i64 > (num_samples - 1) || Count as i64 > (num_samples - 1)
-        {
-            panic!("Requesting samples beyond the stream end");
+        let err = unsafe {
+            FFMS_GetAudio(
+                self.audio_source,
+                buf.as_mut_ptr() as *mut c_void,
+                start as i64,
+                count as i64,
+                error.as_mut_ptr(),
+            )
+        };
+
+        if err != 0 {
+            Err(error)
+        } else {
+            unsafe { buf.set_len(num_elements) };
+            Ok(())
         }
+    }
 
-        let num_channels = audio_prop.Channels;
-        let num_elements = Count * num_channels as usize;
+    /// Decodes samples as `T`, failing when `T` does not match the sample
+    /// format of the stream.
+    pub fn audio_checked<T: Sample>(
+        &self,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<T>, Error> {
+        self.GetAudio(start, count)
+    }
+
+    /// Decodes samples in whatever sample format the stream has.
+    pub fn audio_samples(
+        &self,
+        start: usize,
+        count: usize,
+    ) -> Result<Samples, Error> {
+        let format =
+            SampleFormat::from_i32(self.GetAudioProperties().SampleFormat);
+        Ok(match format {
+            SampleFormat::FMT_U8 => Samples::U8(self.GetAudio(start, count)?),
+            SampleFormat::FMT_S16 => {
+                Samples::S16(self.GetAudio(start, count)?)
+            }
+            SampleFormat::FMT_S32 => {
+                Samples::S32(self.GetAudio(start, count)?)
+            }
+            SampleFormat::FMT_FLT => {
+                Samples::Flt(self.GetAudio(start, count)?)
+            }
+            SampleFormat::FMT_DBL => {
+                Samples::Dbl(self.GetAudio(start, count)?)
+            }
+        })
+    }
 
-        let Buf: Vec<T> = Vec::with_capacity(num_elements);
-        let mut Buf = mem::ManuallyDrop::new(Buf);
-        let buf_ptr = Buf.as_mut_ptr();
+    pub fn decode_block(
+        &self,
+        start: usize,
+        count: usize,
+    ) -> Result<DecodedAudio, Error> {
+        let mut error: Error = Default::default();
+        let audio_prop = self.GetAudioProperties();
+
+        let format = SampleFormat::from_i32(audio_prop.SampleFormat);
+        let channels = audio_prop.Channels as usize;
+        let mut samples =
+            vec![
+                0u8;
+                block_bytes(start, count, &audio_prop.audio_properties)?
+            ];
 
         let err = unsafe {
             FFMS_GetAudio(
                 self.audio_source,
-                buf_ptr as *mut c_void,
-                Start as i64,
-                Count as i64,
+                samples.as_mut_ptr() as *mut c_void,
+                start as i64,
+                count as i64,
                 error.as_mut_ptr(),
             )
         };
@@ -146,12 +591,179 @@ impl AudioSource {
         if err != 0 {
             Err(error)
         } else {
-            let audio_vec = unsafe {
-                Vec::from_raw_parts(buf_ptr, num_elements, num_elements)
-            };
+            Ok(DecodedAudio {
+                samples,
+                format,
+                channels,
+                sample_rate: audio_prop.SampleRate as usize,
+            })
+        }
+    }
+
+    /// Decodes the whole stream one second at a time, calling `progress`
+    /// with the number of samples decoded so far and the total after each
+    /// block.
+    pub fn decode_all_with_progress<F: FnMut(usize, usize)>(
+        &self,
+        progress: F,
+    ) -> Result<DecodedAudio, Error> {
+        let audio_prop = self.GetAudioProperties();
+        let format = SampleFormat::from_i32(audio_prop.SampleFormat);
+        let channels = audio_prop.Channels as usize;
+        let frame_size = channels * format.bytes_per_sample();
+        let total = audio_prop.NumSamples.max(0) as usize;
+        let mut samples =
+            vec![0u8; block_bytes(0, total, &audio_prop.audio_properties)?];
+
+        for_each_block(
+            total,
+            audio_prop.SampleRate as usize,
+            |start, count| {
+                let mut error: Error = Default::default();
+                let buffer = &mut samples[start * frame_size..];
+                let err = unsafe {
+                    FFMS_GetAudio(
+                        self.audio_source,
+                        buffer.as_mut_ptr() as *mut c_void,
+                        start as i64,
+                        count as i64,
+                        error.as_mut_ptr(),
+                    )
+                };
+                if err != 0 {
+                    Err(error)
+                } else {
+                    Ok(())
+                }
+            },
+            progress,
+        )?;
+
+        Ok(DecodedAudio {
+            samples,
+            format,
+            channels,
+            sample_rate: audio_prop.SampleRate as usize,
+        })
+    }
+
+    /// Decodes `count` samples of the given channel indices, one vector per
+    /// requested channel. `T` has to match the sample format of the stream.
+    pub fn extract_channels<T: Sample>(
+        &self,
+        channels: &[usize],
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<Vec<T>>, Error> {
+        let channels_count = self.GetAudioProperties().Channels as usize;
+        check_channels(channels, channels_count)?;
+
+        let samples = self.audio_checked::<T>(start, count)?;
+        Ok(deinterleave(&samples, channels_count, channels))
+    }
+
+    /// Splits the stream into frames of exactly `frame_len` samples per
+    /// channel. The last, shorter frame is zero-padded when `pad` is true
+    /// and dropped otherwise.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `frame_len` is 0.
+    pub fn fixed_frames<T: Sample + Default>(
+        &self,
+        frame_len: usize,
+        pad: bool,
+    ) -> impl Iterator<Item = Result<Vec<T>, Error>> + '_ {
+        assert!(frame_len >= 1, "The frame length must be at least 1");
+        let audio_prop = self.GetAudioProperties();
+        let total = audio_prop.NumSamples.max(0) as usize;
+        let channels = audio_prop.Channels as usize;
+
+        fixed_frame_starts(total, frame_len, pad).map(move |start| {
+            let count = frame_len.min(total - start);
+            let mut samples = self.GetAudio::<T>(start, count)?;
+            samples.resize(frame_len * channels, T::default());
+            Ok(samples)
+        })
+    }
+
+    /// Iterates over the whole stream in chunks of `chunk_samples` samples
+    /// per channel, the last chunk holding whatever is left.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `chunk_samples` is 0.
+    pub fn chunks<T: Sample>(
+        &self,
+        chunk_samples: usize,
+    ) -> AudioChunks<'_, T> {
+        assert!(chunk_samples >= 1, "The chunk length must be at least 1");
+        AudioChunks {
+            audio_source: self,
+            samples_count: self.GetAudioProperties().NumSamples.max(0)
+                as usize,
+            chunk_samples,
+            next_sample: 0,
+            sample: PhantomData,
+        }
+    }
 
-            Ok(audio_vec)
+    /// Decodes samples as `T`, converting the stream to the matching
+    /// sample format first when it has another one.
+    pub fn decode_as<T: Sample>(
+        &mut self,
+        start: usize,
+        count: usize,
+    ) -> Result<Vec<T>, Error> {
+        let format = self.GetAudioProperties().SampleFormat;
+        if let Some(target) = conversion_target::<T>(format) {
+            let mut options = self.CreateResampleOptions();
+            options.set_sample_format(&target);
+            self.SetOutputFormatA(&options)?;
         }
+
+        self.audio_checked(start, count)
+    }
+
+    /// Decodes normalized `f32` samples clamped to `[-1, 1]`, reporting
+    /// whether any sample had to be clamped. NaN samples are replaced by
+    /// silence and reported as well.
+    pub fn audio_normalized_f32_checked(
+        &mut self,
+        start: usize,
+        count: usize,
+    ) -> Result<(Vec<f32>, bool), Error> {
+        let mut samples = self.decode_as::<f32>(start, count)?;
+        let clipped = clip_samples(&mut samples);
+        Ok((samples, clipped))
+    }
+
+    /// Average number of samples per packet of the audio track, exact only
+    /// for codecs with a fixed packet size.
+    pub fn samples_per_packet(&self) -> Option<usize> {
+        let packets = unsafe {
+            FFMS_GetNumFrames(FFMS_GetTrackFromAudio(self.audio_source))
+        };
+        samples_per_packet(self.GetAudioProperties().NumSamples, packets)
+    }
+
+    /// Makes the following reads return audio in `target_format`, mixed to
+    /// `channels`, or to the source layout when `channels` is empty.
+    /// FFMS2 cannot change the sample rate, which stays the source one.
+    pub fn resampled(
+        &mut self,
+        target_format: SampleFormat,
+        channels: &[AudioChannel],
+    ) -> Result<(), Error> {
+        let mut builder =
+            ResampleOptionsBuilder::new(&self.CreateResampleOptions())
+                .sample_format(target_format);
+        if !channels.is_empty() {
+            builder = builder
+                .channel_layout(ChannelLayout::from_channels(channels).0);
+        }
+
+        self.SetOutputFormatA(&builder.build()?)
     }
 
     pub fn CreateResampleOptions(&self) -> ResampleOptions {
@@ -186,6 +798,34 @@ impl AudioSource {
     }
 }
 
+pub struct AudioChunks<'a, T> {
+    audio_source: &'a AudioSource,
+    samples_count: usize,
+    chunk_samples: usize,
+    next_sample: usize,
+    sample: PhantomData<T>,
+}
+
+impl<T: Sample> Iterator for AudioChunks<'_, T> {
+    type Item = Result<Vec<T>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (start, count) = next_chunk(
+            self.next_sample,
+            self.samples_count,
+            self.chunk_samples,
+        )?;
+        self.next_sample += count;
+        Some(self.audio_source.GetAudio(start, count))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = (self.samples_count - self.next_sample)
+            .div_ceil(self.chunk_samples);
+        (remaining, Some(remaining))
+    }
+}
+
 impl Drop for AudioSource {
     fn drop(&mut self) {
         unsafe {
@@ -193,3 +833,363 @@ impl Drop for AudioSource {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decoded_audio_views() {
+        let samples = [1i16, -2, 300, i16::MIN];
+        let decoded = DecodedAudio {
+            samples: samples.iter().flat_map(|s| s.to_ne_bytes()).collect(),
+            format: SampleFormat::FMT_S16,
+            channels: 2,
+            sample_rate: 48000,
+        };
+        assert_eq!(decoded.as_i16().unwrap(), samples);
+        assert!(decoded.as_f32().is_none());
+
+        let samples = [0.5f32, -0.25];
+        let decoded = DecodedAudio {
+            samples: samples.iter().flat_map(|s| s.to_ne_bytes()).collect(),
+            format: SampleFormat::FMT_FLT,
+            channels: 1,
+            sample_rate: 44100,
+        };
+        assert_eq!(decoded.as_f32().unwrap(), samples);
+        assert!(decoded.as_i16().is_none());
+    }
+
+    #[test]
+    fn decoded_block_sizes() {
+        let mut stereo = *AudioProperties::default();
+        stereo.SampleFormat =
+            SampleFormat::to_sample_format(SampleFormat::FMT_S16) as i32;
+        stereo.Channels = 2;
+        stereo.NumSamples = 48000;
+
+        assert_eq!(block_bytes(0, 1024, &stereo).unwrap(), 4096);
+        assert_eq!(block_bytes(47999, 1, &stereo).unwrap(), 4);
+        assert_eq!(block_bytes(48000, 0, &stereo).unwrap(), 0);
+        assert_eq!(
+            block_bytes(48000, 1, &stereo).unwrap_err().sub_type(),
+            Errors::ERROR_INVALID_ARGUMENT
+        );
+        assert!(block_bytes(47000, 1024, &stereo).is_err());
+
+        let mut huge = stereo;
+        huge.NumSamples = i64::MAX;
+        assert!(block_bytes(0, usize::MAX / 2, &huge).is_err());
+    }
+
+    #[test]
+    fn sample_buffers_match_the_stream() {
+        let mut flt = *AudioProperties::default();
+        flt.SampleFormat =
+            SampleFormat::to_sample_format(SampleFormat::FMT_FLT) as i32;
+        flt.Channels = 6;
+        flt.NumSamples = 48000;
+
+        assert_eq!(sample_buffer_len::<f32>(0, 1024, &flt).unwrap(), 6144);
+        // Bytes of a float stream read as `u8` would overflow the buffer
+        let err = sample_buffer_len::<u8>(0, 1024, &flt).unwrap_err();
+        assert_eq!(err.sub_type(), Errors::ERROR_INVALID_ARGUMENT);
+        assert!(sample_buffer_len::<i32>(0, 1024, &flt).is_err());
+        assert!(sample_buffer_len::<f32>(47000, 1024, &flt).is_err());
+
+        let mut huge = flt;
+        huge.NumSamples = i64::MAX;
+        assert!(sample_buffer_len::<f32>(0, usize::MAX / 4, &huge).is_err());
+
+        let mut broken = flt;
+        broken.Channels = -2;
+        assert!(sample_buffer_len::<f32>(0, 1024, &broken).is_err());
+    }
+
+    #[test]
+    fn clip_hot_samples() {
+        let mut samples = [0.5, -1.0, 1.25, -1.5, 1.0];
+        assert!(clip_samples(&mut samples));
+        assert_eq!(samples, [0.5, -1.0, 1.0, -1.0, 1.0]);
+
+        let mut samples = [0.5, -1.0, 1.0];
+        assert!(!clip_samples(&mut samples));
+    }
+
+    #[test]
+    fn clip_non_finite_samples() {
+        let mut samples = [f32::INFINITY, f32::NEG_INFINITY, 0.25];
+        assert!(clip_samples(&mut samples));
+        assert_eq!(samples, [1.0, -1.0, 0.25]);
+
+        let mut samples = [0.25, f32::NAN];
+        assert!(clip_samples(&mut samples));
+        assert_eq!(samples, [0.25, 0.0]);
+
+        // A full scale square wave peaks exactly at the limits
+        let mut square: Vec<f32> = (0..64)
+            .map(|n| if n % 2 == 0 { 1.0 } else { -1.0 })
+            .collect();
+        assert!(!clip_samples(&mut square));
+    }
+
+    #[test]
+    fn deinterleave_center_channel() {
+        // Three frames of 5.1 audio, the channel index in the tens
+        let samples: Vec<i16> = (0..3)
+            .flat_map(|frame| (0..6).map(move |channel| channel * 10 + frame))
+            .collect();
+        assert_eq!(deinterleave(&samples, 6, &[2]), [vec![20, 21, 22]]);
+        assert_eq!(
+            deinterleave(&samples, 6, &[0, 1]),
+            [vec![0, 1, 2], vec![10, 11, 12]]
+        );
+    }
+
+    #[test]
+    fn missing_channels_rejected() {
+        assert!(check_channels(&[2], 6).is_ok());
+        assert!(check_channels(&[0, 5], 6).is_ok());
+        assert!(check_channels(&[], 2).is_ok());
+        assert_eq!(
+            check_channels(&[0, 6], 6).unwrap_err().sub_type(),
+            Errors::ERROR_INVALID_ARGUMENT
+        );
+        assert!(check_channels(&[0], 0).is_err());
+    }
+
+    #[test]
+    fn aac_samples_per_packet() {
+        // 10 seconds of 48 kHz AAC, the last packet partly filled
+        assert_eq!(samples_per_packet(480_000, 469), Some(1023));
+        assert_eq!(samples_per_packet(1024 * 469, 469), Some(1024));
+        assert_eq!(samples_per_packet(480_000, 0), None);
+        assert_eq!(samples_per_packet(480_000, -1), None);
+        assert_eq!(samples_per_packet(-1, 469), None);
+    }
+
+    #[test]
+    fn reused_buffer_keeps_capacity() {
+        let mut buf: Vec<f32> = Vec::new();
+        prepare_buffer(&mut buf, 2048);
+        buf.extend(std::iter::repeat_n(0.5, 2048));
+        let capacity = buf.capacity();
+        let data = buf.as_ptr();
+
+        prepare_buffer(&mut buf, 2048);
+        assert!(buf.is_empty());
+        assert_eq!(buf.capacity(), capacity);
+        assert_eq!(buf.as_ptr(), data);
+
+        prepare_buffer(&mut buf, 4096);
+        assert!(buf.capacity() >= 4096);
+    }
+
+    #[test]
+    fn mismatched_sample_format() {
+        let s16 = SampleFormat::to_sample_format(SampleFormat::FMT_S16) as i32;
+        let err = check_sample_format::<f32>(s16).unwrap_err();
+        assert_eq!(err.sub_type(), Errors::ERROR_INVALID_ARGUMENT);
+        assert!(check_sample_format::<i32>(s16).is_err());
+        assert!(check_sample_format::<i16>(s16).is_ok());
+    }
+
+    #[test]
+    fn each_sample_type_matches_one_format() {
+        use SampleFormat::*;
+
+        let formats = [FMT_U8, FMT_S16, FMT_S32, FMT_FLT, FMT_DBL];
+        let accepted = |check: fn(i32) -> Result<(), Error>| {
+            formats
+                .into_iter()
+                .filter(|format| {
+                    check(SampleFormat::to_sample_format(*format) as i32)
+                        .is_ok()
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(accepted(check_sample_format::<u8>), [FMT_U8]);
+        assert_eq!(accepted(check_sample_format::<i16>), [FMT_S16]);
+        assert_eq!(accepted(check_sample_format::<i32>), [FMT_S32]);
+        assert_eq!(accepted(check_sample_format::<f32>), [FMT_FLT]);
+        assert_eq!(accepted(check_sample_format::<f64>), [FMT_DBL]);
+    }
+
+    #[test]
+    fn s16_decoded_as_f32() {
+        let s16 = SampleFormat::to_sample_format(SampleFormat::FMT_S16) as i32;
+        assert_eq!(conversion_target::<f32>(s16), Some(SampleFormat::FMT_FLT));
+        assert_eq!(conversion_target::<f64>(s16), Some(SampleFormat::FMT_DBL));
+        assert_eq!(conversion_target::<i16>(s16), None);
+
+        let flt = SampleFormat::to_sample_format(SampleFormat::FMT_FLT) as i32;
+        assert_eq!(conversion_target::<f32>(flt), None);
+    }
+
+    #[test]
+    fn chunks_cover_the_stream() {
+        let stream: Vec<i16> = (0..1000).collect();
+        let mut next = 0;
+        let mut concatenated = Vec::new();
+        while let Some((start, count)) = next_chunk(next, stream.len(), 300) {
+            concatenated.extend_from_slice(&stream[start..start + count]);
+            next += count;
+        }
+        assert_eq!(concatenated, stream);
+        assert_eq!(next_chunk(900, 1000, 300), Some((900, 100)));
+        assert_eq!(next_chunk(1000, 1000, 300), None);
+    }
+
+    #[test]
+    fn fixed_frame_splits() {
+        assert!(fixed_frame_starts(2500, 1000, true).eq([0, 1000, 2000]));
+        assert!(fixed_frame_starts(2500, 1000, false).eq([0, 1000]));
+        assert!(fixed_frame_starts(2000, 1000, true).eq([0, 1000]));
+        assert_eq!(fixed_frame_starts(500, 1000, false).count(), 0);
+    }
+
+    #[test]
+    fn layout_from_channels() {
+        let stereo =
+            [AudioChannel::CH_FRONT_LEFT, AudioChannel::CH_FRONT_RIGHT];
+        let layout = ChannelLayout::from_channels(&stereo);
+        assert_eq!(layout.name(), "stereo");
+        assert_eq!(layout.channels().unwrap(), stereo);
+        assert_eq!(ChannelLayout::from_channels(&[]), ChannelLayout(0));
+    }
+
+    #[test]
+    #[ignore = "needs a 44.1 kHz audio file in FFMS2_TEST_AUDIO"]
+    fn resampled_source() {
+        let path = std::env::var_os("FFMS2_TEST_AUDIO").unwrap();
+        let path = Path::new(&path);
+        FFMS2::Init();
+        let indexer = Indexer::new(path).unwrap();
+        indexer.enable_track_type(TrackType::TYPE_AUDIO).unwrap();
+        let index = indexer.do_indexing(IndexErrorHandling::Abort).unwrap();
+        let track = index
+            .first_indexed_track_of_type(TrackType::TYPE_AUDIO)
+            .unwrap();
+        let delay = FFMS_AudioDelayModes::FFMS_DELAY_NO_SHIFT as isize;
+        let mut audio = AudioSource::new(path, track, &index, delay).unwrap();
+        let source = audio.GetAudioProperties();
+        assert_eq!(source.SampleRate, 44100);
+
+        // FFMS2 refuses to resample 44.1 kHz to 16 kHz
+        let to_16k =
+            ResampleOptionsBuilder::new(&audio.CreateResampleOptions())
+                .sample_rate(16000)
+                .build()
+                .unwrap();
+        let err = audio.SetOutputFormatA(&to_16k).unwrap_err();
+        assert_eq!(err.sub_type(), Errors::ERROR_UNSUPPORTED);
+
+        audio
+            .resampled(SampleFormat::FMT_FLT, &[AudioChannel::CH_FRONT_CENTER])
+            .unwrap();
+        let mono = audio.GetAudioProperties();
+        assert_eq!(mono.SampleRate, 44100);
+        assert_eq!(mono.NumSamples, source.NumSamples);
+        assert_eq!(mono.Channels, 1);
+        assert_eq!(audio.audio_checked::<f32>(0, 1024).unwrap().len(), 1024);
+    }
+
+    #[test]
+    fn channel_map_5_1() {
+        use AudioChannel::*;
+        let layout = 0x1 | 0x2 | 0x4 | 0x8 | 0x10 | 0x20;
+        assert_eq!(
+            AudioChannel::channel_map(layout).unwrap(),
+            [
+                CH_FRONT_LEFT,
+                CH_FRONT_RIGHT,
+                CH_FRONT_CENTER,
+                CH_LOW_FREQUENCY,
+                CH_BACK_LEFT,
+                CH_BACK_RIGHT,
+            ]
+        );
+        assert!(AudioChannel::channel_map(0).is_none());
+    }
+
+    #[test]
+    fn sample_range() {
+        assert!(check_sample_range(999, 1, 1000).is_ok());
+        assert!(check_sample_range(0, 1000, 1000).is_ok());
+        assert!(check_sample_range(1000, 0, 1000).is_ok());
+        assert!(check_sample_range(999, 2, 1000).is_err());
+        assert!(check_sample_range(1000, 1, 1000).is_err());
+        assert!(check_sample_range(usize::MAX, 1, 1000).is_err());
+    }
+
+    #[test]
+    fn channel_layout_names() {
+        assert_eq!(ChannelLayout(0x3).name(), "stereo");
+        assert_eq!(ChannelLayout(0x3F).name(), "5.1");
+        assert_eq!(ChannelLayout(0x63F).name(), "7.1");
+        assert_eq!(ChannelLayout(0x1 | 0x8).name(), "custom");
+    }
+
+    #[test]
+    fn block_progress() {
+        let mut blocks = Vec::new();
+        let mut reports = Vec::new();
+        for_each_block(
+            10,
+            4,
+            |start, count| {
+                blocks.push((start, count));
+                Ok(())
+            },
+            |done, total| reports.push((done, total)),
+        )
+        .unwrap();
+
+        assert_eq!(blocks, [(0, 4), (4, 4), (8, 2)]);
+        assert_eq!(reports, [(4, 10), (8, 10), (10, 10)]);
+    }
+
+    #[test]
+    fn block_error_stops_progress() {
+        let mut reports = Vec::new();
+        let result = for_each_block(
+            10,
+            4,
+            |start, _| {
+                if start == 4 {
+                    Err(Error::new(
+                        Errors::ERROR_DECODING,
+                        Errors::ERROR_CODEC,
+                        "Corrupt packet",
+                    ))
+                } else {
+                    Ok(())
+                }
+            },
+            |done, total| reports.push((done, total)),
+        );
+
+        assert_eq!(result.unwrap_err().sub_type(), Errors::ERROR_CODEC);
+        assert_eq!(reports, [(4, 10)]);
+    }
+
+    #[test]
+    fn block_progress_edge_cases() {
+        // A sample rate of 0 still makes progress one sample at a time
+        let mut reports = Vec::new();
+        for_each_block(
+            3,
+            0,
+            |_, _| Ok(()),
+            |done, total| reports.push((done, total)),
+        )
+        .unwrap();
+        assert_eq!(reports, [(1, 3), (2, 3), (3, 3)]);
+
+        let mut called = false;
+        for_each_block(0, 48000, |_, _| Ok(()), |_, _| called = true).unwrap();
+        assert!(!called);
+    }
+}
@@ -1,8 +1,8 @@
+use crate::pixel::*;
+use crate::track::*;
 use crate::video::*;
 use crate::*;
 
-use ffmpeg_the_third::ffi::AVPixelFormat;
-use ffmpeg_the_third::format::Pixel;
 use ffms2_sys::*;
 
 use std::ffi::CString;
@@ -28,6 +28,70 @@ create_enum!(
     )
 );
 
+impl Resizers {
+    /// Rough output quality from 0 to 10, to rank resizers in a UI.
+    pub fn quality(self) -> u8 {
+        use Resizers::*;
+        match self {
+            RESIZER_POINT => 1,
+            RESIZER_FAST_BILINEAR => 2,
+            RESIZER_AREA => 4,
+            RESIZER_BILINEAR => 4,
+            RESIZER_X => 5,
+            RESIZER_BICUBLIN => 5,
+            RESIZER_BICUBIC => 6,
+            RESIZER_GAUSS => 6,
+            RESIZER_SPLINE => 9,
+            RESIZER_LANCZOS => 9,
+            RESIZER_SINC => 10,
+        }
+    }
+
+    /// Rough speed from 0 to 10, to rank resizers in a UI.
+    pub fn speed(self) -> u8 {
+        use Resizers::*;
+        match self {
+            RESIZER_POINT => 10,
+            RESIZER_FAST_BILINEAR => 9,
+            RESIZER_BILINEAR => 7,
+            RESIZER_AREA => 7,
+            RESIZER_BICUBLIN => 6,
+            RESIZER_X => 5,
+            RESIZER_BICUBIC => 5,
+            RESIZER_GAUSS => 4,
+            RESIZER_SPLINE => 3,
+            RESIZER_LANCZOS => 3,
+            RESIZER_SINC => 1,
+        }
+    }
+}
+
+simple_enum!(ScalingDirection, (Up, Down, None, Mixed));
+
+impl ScalingDirection {
+    pub fn new(source: (usize, usize), target: (usize, usize)) -> Self {
+        use std::cmp::Ordering::*;
+        match (target.0.cmp(&source.0), target.1.cmp(&source.1)) {
+            (Equal, Equal) => ScalingDirection::None,
+            (Greater | Equal, Greater | Equal) => ScalingDirection::Up,
+            (Less | Equal, Less | Equal) => ScalingDirection::Down,
+            _ => ScalingDirection::Mixed,
+        }
+    }
+}
+
+impl Resizers {
+    /// Resizer giving good results for the direction of the scaling.
+    pub fn recommended(direction: ScalingDirection) -> Self {
+        match direction {
+            ScalingDirection::Up => Resizers::RESIZER_LANCZOS,
+            ScalingDirection::Down => Resizers::RESIZER_AREA,
+            ScalingDirection::None => Resizers::RESIZER_POINT,
+            ScalingDirection::Mixed => Resizers::RESIZER_BICUBIC,
+        }
+    }
+}
+
 simple_enum!(
     ChromaLocations,
     (
@@ -41,6 +105,60 @@ simple_enum!(
     )
 );
 
+// BT.601 is SMPTE170M for NTSC and BT470BG for PAL
+numbered_enum!(
+    ColorPrimaries,
+    UNSPECIFIED: 2,
+    (
+        BT709: 1,
+        BT470M: 4,
+        BT470BG: 5,
+        SMPTE170M: 6,
+        SMPTE240M: 7,
+        FILM: 8,
+        BT2020: 9,
+        SMPTE428: 10,
+        SMPTE431: 11,
+        SMPTE432: 12,
+        EBU3213: 22,
+    )
+);
+
+// SMPTE2084 is PQ and ARIB_STD_B67 is HLG
+numbered_enum!(
+    TransferCharacteristics,
+    UNSPECIFIED: 2,
+    (
+        BT709: 1,
+        GAMMA22: 4,
+        GAMMA28: 5,
+        SMPTE170M: 6,
+        SMPTE240M: 7,
+        LINEAR: 8,
+        LOG: 9,
+        LOG_SQRT: 10,
+        IEC61966_2_4: 11,
+        BT1361_ECG: 12,
+        IEC61966_2_1: 13,
+        BT2020_10: 14,
+        BT2020_12: 15,
+        SMPTE2084: 16,
+        SMPTE428: 17,
+        ARIB_STD_B67: 18,
+    )
+);
+
+impl TransferCharacteristics {
+    /// Whether the transfer is one of the HDR ones, PQ or HLG.
+    pub fn is_hdr(self) -> bool {
+        matches!(
+            self,
+            TransferCharacteristics::SMPTE2084
+                | TransferCharacteristics::ARIB_STD_B67
+        )
+    }
+}
+
 create_struct!(
     FrameInfo,
     frame_info,
@@ -49,11 +167,50 @@ create_struct!(
     (0, 0, 0, 0)
 );
 
+serde_struct!(
+    FrameInfo,
+    frame_info,
+    (PTS, RepeatPict, KeyFrame, OriginalPTS)
+);
+
 impl FrameInfo {
     pub fn KeyFrame(&self) -> usize {
         self.frame_info.KeyFrame as usize
     }
 
+    pub fn timestamp_seconds(&self, time_base: &TrackTimeBase) -> f64 {
+        self.frame_info.PTS as f64 * time_base.Num as f64
+            / time_base.Den as f64
+            / 1000.0
+    }
+
+    /// Presentation time formatted as `HH:MM:SS.mmm`, the timestamp format
+    /// of WebVTT subtitles and chapter files.
+    pub fn to_timecode(&self, time_base: &TrackTimeBase) -> String {
+        let ms = (self.frame_info.PTS as i128 * time_base.Num as i128)
+            .checked_div(time_base.Den as i128)
+            .unwrap_or(0);
+        let sign = if ms < 0 { "-" } else { "" };
+        let ms = ms.unsigned_abs();
+        format!(
+            "{}{:02}:{:02}:{:02}.{:03}",
+            sign,
+            ms / 3_600_000,
+            ms / 60_000 % 60,
+            ms / 1000 % 60,
+            ms % 1000
+        )
+    }
+
+    pub fn original_timestamp_seconds(
+        &self,
+        time_base: &TrackTimeBase,
+    ) -> f64 {
+        self.frame_info.OriginalPTS as f64 * time_base.Num as f64
+            / time_base.Den as f64
+            / 1000.0
+    }
+
     pub(crate) fn create_struct(frame_info: &FFMS_FrameInfo) -> Self {
         FrameInfo {
             frame_info: *frame_info,
@@ -61,6 +218,10 @@ impl FrameInfo {
     }
 }
 
+// The FFMS2 API these bindings are generated from predates the Dolby Vision
+// RPU and HDR10+ side data fields, so only the static HDR metadata below is
+// available on frames. FFMS2 does not export any other side data either, A53
+// closed captions included, so there is no way to read captions from here.
 create_struct!(
     Frame,
     frame,
@@ -126,16 +287,106 @@ create_struct!(
         0,
         0,
         0
-    )
+    ),
+    read_only
 );
 
+pub const PLANES_COUNT: usize = 4;
+
+// 256 colors of 4 bytes, whatever the linesize of the plane
+const PALETTE_SIZE: usize = 1024;
+
+/// Highest luma value still considered black by `Frame::is_black`, above
+/// the limited range black level of 16 to absorb encoding noise.
+pub const BLACK_LUMA_THRESHOLD: u8 = 32;
+
+pub struct OwnedFrame {
+    frame: Frame,
+    planes: [Vec<u8>; 4],
+}
+
+impl OwnedFrame {
+    pub fn new(frame: &Frame) -> Self {
+        let planes = frame
+            .planes()
+            .map(|plane| plane.map_or_else(Vec::new, <[u8]>::to_vec));
+
+        let mut owned = OwnedFrame {
+            frame: Frame { frame: **frame },
+            planes,
+        };
+        for i in 0..4 {
+            owned.frame.frame.Data[i] = if owned.planes[i].is_empty() {
+                ptr::null()
+            } else {
+                owned.planes[i].as_ptr()
+            };
+        }
+        owned
+    }
+}
+
+impl std::ops::Deref for OwnedFrame {
+    type Target = Frame;
+
+    fn deref(&self) -> &Self::Target {
+        &self.frame
+    }
+}
+
+fn fit_within(
+    width: usize,
+    height: usize,
+    max_w: usize,
+    max_h: usize,
+) -> (usize, usize) {
+    if width == 0 || height == 0 || max_w == 0 || max_h == 0 {
+        return (0, 0);
+    }
+    if width <= max_w && height <= max_h {
+        return (width, height);
+    }
+
+    // Compare max_w / width and max_h / height without rounding
+    if max_w * height <= max_h * width {
+        (max_w, (height * max_w / width).max(1))
+    } else {
+        ((width * max_h / height).max(1), max_h)
+    }
+}
+
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct FrameResolution {
     pub width: i32,
     pub height: i32,
 }
 
 impl Frame {
+    pub fn color_range(&self) -> ColorRanges {
+        ColorRanges::new(self.frame.ColorRange)
+    }
+
+    pub fn color_primaries(&self) -> ColorPrimaries {
+        ColorPrimaries::new(self.frame.ColorPrimaries)
+    }
+
+    pub fn transfer_characteristics(&self) -> TransferCharacteristics {
+        TransferCharacteristics::new(self.frame.TransferCharateristics)
+    }
+
     pub fn GetFrame(V: &mut VideoSource, n: usize) -> Result<Self, Error> {
+        let frame_count = V.frame_count();
+        if n >= frame_count {
+            return Err(Error::new(
+                Errors::ERROR_DECODING,
+                Errors::ERROR_INVALID_ARGUMENT,
+                &format!(
+                    "Frame {} is out of range, the track has {} frames",
+                    n, frame_count
+                ),
+            ));
+        }
+
         let mut error: Error = Default::default();
 
         let c_frame = unsafe {
@@ -147,7 +398,7 @@ impl Frame {
         } else {
             let ref_frame = unsafe { &*c_frame };
 
-            Ok(Frame { frame: *ref_frame })
+            Ok(Frame::create_frame(ref_frame))
         }
     }
 
@@ -166,7 +417,7 @@ impl Frame {
         } else {
             let ref_frame = unsafe { &*c_frame };
 
-            Ok(Frame { frame: *ref_frame })
+            Ok(Frame::create_frame(ref_frame))
         }
     }
 
@@ -175,7 +426,68 @@ impl Frame {
         unsafe { FFMS_GetPixFmt(source.as_ptr()) }
     }
 
-    pub fn set_data(&mut self, data: [&[u8]; 4]) {
+    /// Looks up a pixel format by its FFmpeg name. Unlike `GetPixFmt`, an
+    /// unknown name or a format `PixelFormat` does not describe is an error
+    /// rather than `PixelFormat::NONE`, which only an empty name maps to.
+    pub fn pixel_format(name: &str) -> Result<PixelFormat, Error> {
+        if name.is_empty() {
+            return Ok(PixelFormat::NONE);
+        }
+
+        let id = match CString::new(name) {
+            Ok(source) => unsafe { FFMS_GetPixFmt(source.as_ptr()) },
+            Err(_) => -1,
+        };
+        if id == -1 {
+            return Err(Error::new(
+                Errors::ERROR_SCALING,
+                Errors::ERROR_INVALID_ARGUMENT,
+                &format!("Unknown pixel format `{}`", name),
+            ));
+        }
+
+        match PixelFormat::new(id) {
+            PixelFormat::NONE => Err(Error::new(
+                Errors::ERROR_SCALING,
+                Errors::ERROR_UNSUPPORTED,
+                &format!("Unsupported pixel format `{}`", name),
+            )),
+            pixel_format => Ok(pixel_format),
+        }
+    }
+
+    pub(crate) fn create_frame(frame: &FFMS_Frame) -> Self {
+        let mut frame = *frame;
+
+        if frame.HasMasteringDisplayPrimaries == 0 {
+            frame.MasteringDisplayPrimariesX = [0.0; 3];
+            frame.MasteringDisplayPrimariesY = [0.0; 3];
+            frame.MasteringDisplayWhitePointX = 0.0;
+            frame.MasteringDisplayWhitePointY = 0.0;
+        }
+
+        if frame.HasMasteringDisplayLuminance == 0 {
+            frame.MasteringDisplayMinLuminance = 0.0;
+            frame.MasteringDisplayMaxLuminance = 0.0;
+        }
+
+        if frame.HasContentLightLevel == 0 {
+            frame.ContentLightLevelMax = 0;
+            frame.ContentLightLevelAverage = 0;
+        }
+
+        Frame { frame }
+    }
+
+    /// Points the planes of the frame at `data`.
+    ///
+    /// # Safety
+    ///
+    /// The frame keeps raw pointers to `data` without borrowing it, so the
+    /// planes must outlive the frame and every copy of it, and hold as many
+    /// bytes as `Frame::planes` reads for the linesizes, size and pixel
+    /// format of the frame.
+    pub unsafe fn set_data(&mut self, data: [&[u8]; 4]) {
         self.frame.Data = [
             data[0].as_ptr(),
             data[1].as_ptr(),
@@ -199,50 +511,313 @@ impl Frame {
         FrameResolution { width, height }
     }
 
-    pub fn get_pixel_data(&self) -> Option<Vec<Option<&[u8]>>> {
-        let data = self.frame.Data;
-        let num_planes = 4;
-        let mut data_vec = Vec::with_capacity(num_planes);
-        let linesize = self.frame.Linesize;
-
-        // This is not good, but we can't think of any better way to do this.
-        // See https://github.com/rust-av/ffms2-rs/pull/29#discussion_r1115397695
-        // What we've considered:
-        // 1. A large match statement mapping the i32 (from FFMS2) to the AVPixelFormat enum.
-        //    This is an unreasonable amount of work since the AVPixelFormat enum is different
-        //    across versions and build configurations of FFmpeg, all of which we would need to support.
-        // 2. Parsing the pixel format string.
-        //    Although FFMS2 provides a function to get the i32 from the pixel format string,
-        //    there's no function for the other way around.
-        // 3. Making a PR in FFMS2 to expose chrome height in the frame struct.
-        //    This is the best solution; we just gotta find someone to do it.
-        let pix_fmt: AVPixelFormat =
-            unsafe { mem::transmute(self.frame.EncodedPixelFormat) };
-
-        let log2_chroma_h = match Pixel::from(pix_fmt).descriptor() {
-            Some(pix_fmt_descriptor) => pix_fmt_descriptor.log2_chroma_h(),
-            None => return None,
+    pub fn matches_output(
+        &self,
+        width: usize,
+        height: usize,
+        pixel_format: PixelFormat,
+    ) -> bool {
+        let resolution = self.get_frame_resolution();
+        resolution.width as usize == width
+            && resolution.height as usize == height
+            && self.frame.ConvertedPixelFormat == pixel_format.to_pix_fmt()
+    }
+
+    /// Width and height in pixels of a plane. Semi-planar formats store
+    /// interleaved U and V samples for each pixel of their second plane, and
+    /// the second plane of `PAL8` is a palette of 256 colors. `None` for
+    /// planes the pixel format does not have and for formats this crate
+    /// does not describe.
+    pub fn plane_dimensions(&self, plane: usize) -> Option<(usize, usize)> {
+        let pixel_format = PixelFormat::new(self.frame.ConvertedPixelFormat);
+        if plane >= pixel_format.planes_count()
+            || self.frame.Linesize[plane] == 0
+        {
+            return None;
+        }
+
+        let resolution = self.get_frame_resolution();
+        let width = usize::try_from(resolution.width).ok()?;
+        let height = usize::try_from(resolution.height).ok()?;
+        if pixel_format == PixelFormat::PAL8 && plane == 1 {
+            return Some((256, 1));
+        }
+
+        match pixel_format.chroma_subsampling() {
+            Some((log2_chroma_w, log2_chroma_h))
+                if plane == 1 || plane == 2 =>
+            {
+                Some((
+                    width.div_ceil(1 << log2_chroma_w),
+                    height.div_ceil(1 << log2_chroma_h),
+                ))
+            }
+            _ => Some((width, height)),
+        }
+    }
+
+    // Bytes of a plane including the row padding
+    fn plane_len(&self, plane: usize) -> Option<usize> {
+        let (_, height) = self.plane_dimensions(plane)?;
+        let linesize = self.frame.Linesize[plane];
+        if linesize <= 0 || self.frame.Data[plane].is_null() {
+            return None;
+        }
+
+        let pixel_format = PixelFormat::new(self.frame.ConvertedPixelFormat);
+        if pixel_format == PixelFormat::PAL8 && plane == 1 {
+            Some(PALETTE_SIZE)
+        } else {
+            (linesize as usize).checked_mul(height)
+        }
+    }
+
+    // Rows of a plane cut to their first `row_bytes` bytes, `None` when the
+    // plane is missing or its lines are shorter
+    fn plane_rows(
+        &self,
+        plane: usize,
+        row_bytes: usize,
+    ) -> Option<impl Iterator<Item = &[u8]>> {
+        let data = self.planes()[plane]?;
+        let linesize = self.frame.Linesize[plane] as usize;
+        (linesize >= row_bytes)
+            .then(|| data.chunks(linesize).map(move |row| &row[..row_bytes]))
+    }
+
+    /// Unpacks P010, P012 and P016 frames into separate Y, U and V planes of
+    /// native `u16` values, shifting the samples out of the high bits.
+    pub fn p010_to_u16_planes(&self) -> Option<[Vec<u16>; 3]> {
+        let shift = match PixelFormat::new(self.frame.ConvertedPixelFormat) {
+            PixelFormat::P010LE => 6,
+            PixelFormat::P012LE => 4,
+            PixelFormat::P016LE => 0,
+            _ => return None,
         };
 
-        for i in 0..num_planes {
-            if linesize[i] == 0 {
-                data_vec.push(None);
-            } else {
-                let sub_h = if i == 0 { 0 } else { log2_chroma_h };
-                let plane_slice_length =
-                    linesize[i] * self.EncodedHeight >> sub_h;
-                let plane_slice = unsafe {
-                    slice::from_raw_parts(data[i], plane_slice_length as usize)
-                };
-
-                data_vec.push(Some(plane_slice));
+        let read_plane =
+            |plane: usize| {
+                let (width, height) = self.plane_dimensions(plane)?;
+                let samples = if plane == 0 { width } else { 2 * width };
+                let rows = self.plane_rows(plane, 2 * samples)?;
+                let mut words = Vec::with_capacity(samples * height);
+                words.extend(rows.flat_map(|row| row.chunks_exact(2)).map(
+                    |word| u16::from_le_bytes([word[0], word[1]]) >> shift,
+                ));
+                Some(words)
+            };
+
+        let luma = read_plane(0)?;
+        let chroma = read_plane(1)?;
+        let u = chroma.iter().step_by(2).copied().collect();
+        let v = chroma.iter().skip(1).step_by(2).copied().collect();
+
+        Some([luma, u, v])
+    }
+
+    /// Unpacks `X2RGB10LE` and `X2BGR10LE` frames into 10-bit R, G and B
+    /// values, three `u16` per pixel in RGB order whatever the source order.
+    pub fn x2rgb10_to_u16_rgb(&self) -> Option<Vec<u16>> {
+        // Shifts of the red and blue components in each 32-bit word
+        let (red, blue) =
+            match PixelFormat::new(self.frame.ConvertedPixelFormat) {
+                PixelFormat::X2RGB10LE => (20, 0),
+                PixelFormat::X2BGR10LE => (0, 20),
+                _ => return None,
+            };
+
+        let (width, _) = self.plane_dimensions(0)?;
+        let rows = self.plane_rows(0, 4 * width)?;
+        let component =
+            |word: u32, shift: u32| ((word >> shift) & 0x3ff) as u16;
+
+        Some(
+            rows.flat_map(|row| row.chunks_exact(4))
+                .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+                .flat_map(|word| {
+                    [
+                        component(word, red),
+                        component(word, 10),
+                        component(word, blue),
+                    ]
+                })
+                .collect(),
+        )
+    }
+
+    pub fn unpack_mono(&self) -> Option<Vec<u8>> {
+        let white_bit = match PixelFormat::new(self.frame.ConvertedPixelFormat)
+        {
+            PixelFormat::MONOWHITE => 0,
+            PixelFormat::MONOBLACK => 1,
+            _ => return None,
+        };
+
+        let (width, height) = self.plane_dimensions(0)?;
+        let rows = self.plane_rows(0, width.div_ceil(8))?;
+
+        let mut pixels = Vec::with_capacity(width * height);
+        for row in rows {
+            for x in 0..width {
+                let bit = (row[x / 8] >> (7 - x % 8)) & 1;
+                pixels.push(if bit == white_bit { 255 } else { 0 });
+            }
+        }
+
+        Some(pixels)
+    }
+
+    /// Full resolution Y plane without row padding, for 8-bit YUV and
+    /// grayscale formats.
+    pub fn luma(&self) -> Option<Vec<u8>> {
+        use PixelFormat::*;
+        // Position of the first luma sample and distance between samples
+        let (offset, step) =
+            match PixelFormat::new(self.frame.ConvertedPixelFormat) {
+                YUV420P | YUV422P | YUV444P | YUV410P | YUV411P | YUV440P
+                | YUVJ420P | YUVJ422P | YUVJ444P | YUVA420P | YUVA444P
+                | NV12 | NV21 | NV16 | GRAY8 => (0, 1),
+                YUYV422 => (0, 2),
+                UYVY422 => (1, 2),
+                _ => return None,
+            };
+
+        let (width, _) = self.plane_dimensions(0)?;
+        let rows = self.plane_rows(0, step * width)?;
+
+        Some(
+            rows.flat_map(|row| row[offset..].iter().step_by(step))
+                .copied()
+                .collect(),
+        )
+    }
+
+    /// Size of the previews made by `downscale_nearest`: the largest size
+    /// fitting in `max_w` x `max_h` with the aspect ratio of the frame, never
+    /// larger than the frame itself.
+    pub fn downscaled_size(
+        &self,
+        max_w: usize,
+        max_h: usize,
+    ) -> (usize, usize) {
+        let resolution = self.get_frame_resolution();
+        fit_within(
+            resolution.width.max(0) as usize,
+            resolution.height.max(0) as usize,
+            max_w,
+            max_h,
+        )
+    }
+
+    /// Nearest-neighbor preview of a packed 8-bit RGB frame, computed on the
+    /// decoded data without reconfiguring the scaler. The pixels keep the
+    /// format of the frame and are sized by `downscaled_size`.
+    pub fn downscale_nearest(
+        &self,
+        max_w: usize,
+        max_h: usize,
+    ) -> Option<Vec<u8>> {
+        use PixelFormat::*;
+        let pixel_format = PixelFormat::new(self.frame.ConvertedPixelFormat);
+        if !matches!(pixel_format, RGB24 | BGR24 | RGBA | BGRA | ARGB | ABGR) {
+            return None;
+        }
+
+        let bytes_per_pixel = pixel_format.components() as usize;
+        let (width, height) = self.plane_dimensions(0)?;
+        let rows: Vec<_> =
+            self.plane_rows(0, width * bytes_per_pixel)?.collect();
+        let (out_w, out_h) = self.downscaled_size(max_w, max_h);
+
+        let mut pixels = Vec::with_capacity(out_w * out_h * bytes_per_pixel);
+        for y in 0..out_h {
+            let row = rows[y * height / out_h];
+            for x in 0..out_w {
+                let offset = x * width / out_w * bytes_per_pixel;
+                pixels
+                    .extend_from_slice(&row[offset..offset + bytes_per_pixel]);
+            }
+        }
+
+        Some(pixels)
+    }
+
+    /// Whether every luma sample is within `tolerance` of the first one.
+    /// Always false for empty frames and for formats without 8-bit luma,
+    /// see `Frame::luma`.
+    pub fn is_uniform(&self, tolerance: u8) -> bool {
+        self.luma().is_some_and(|luma| {
+            luma.first().is_some_and(|first| {
+                luma.iter()
+                    .all(|sample| sample.abs_diff(*first) <= tolerance)
+            })
+        })
+    }
+
+    /// Whether no luma sample exceeds `BLACK_LUMA_THRESHOLD`. Always false
+    /// for empty frames.
+    pub fn is_black(&self) -> bool {
+        self.luma().is_some_and(|luma| {
+            !luma.is_empty()
+                && luma.iter().all(|sample| *sample <= BLACK_LUMA_THRESHOLD)
+        })
+    }
+
+    /// Splits packed 4:2:2 `YUYV422` (`Y Cb Y Cr`) and `UYVY422`
+    /// (`Cb Y Cr Y`) frames into Y, U and V planes without row padding.
+    pub fn packed_yuv_to_planar(&self) -> Option<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        // Offsets of Y0, Cb, Y1 and Cr in each 4 bytes group
+        let [y0, u, y1, v] =
+            match PixelFormat::new(self.frame.ConvertedPixelFormat) {
+                PixelFormat::YUYV422 => [0, 1, 2, 3],
+                PixelFormat::UYVY422 => [1, 0, 3, 2],
+                _ => return None,
+            };
+
+        let (width, height) = self.plane_dimensions(0)?;
+        let chroma_width = width.div_ceil(2);
+        let rows = self.plane_rows(0, 4 * chroma_width)?;
+
+        let mut luma = Vec::with_capacity(width * height);
+        let mut cb = Vec::with_capacity(chroma_width * height);
+        let mut cr = Vec::with_capacity(chroma_width * height);
+        for row in rows {
+            for (x, group) in row.chunks_exact(4).enumerate() {
+                luma.push(group[y0]);
+                if 2 * x + 1 < width {
+                    luma.push(group[y1]);
+                }
+                cb.push(group[u]);
+                cr.push(group[v]);
             }
         }
 
-        Some(data_vec)
+        Some((luma, cb, cr))
+    }
+
+    /// Data of each plane, `linesize * plane height` bytes long including
+    /// the row padding, with the plane heights of `plane_dimensions`. The
+    /// palette of `PAL8` frames is 1024 bytes long. Every plane is `None`
+    /// for formats this crate does not describe. The slices borrow the
+    /// buffers of the `VideoSource` and are only valid until the next frame
+    /// is decoded from it.
+    pub fn planes(&self) -> [Option<&[u8]>; PLANES_COUNT] {
+        std::array::from_fn(|i| {
+            let len = self.plane_len(i)?;
+            Some(unsafe { slice::from_raw_parts(self.frame.Data[i], len) })
+        })
+    }
+
+    #[deprecated(note = "use `Frame::planes`")]
+    pub fn get_pixel_data(&self) -> Option<Vec<Option<&[u8]>>> {
+        Some(self.planes().to_vec())
     }
 
-    pub fn set_LineSize(&mut self, linesize: &[usize; 4]) {
+    /// # Safety
+    ///
+    /// The planes set through `set_data` must hold as many bytes as
+    /// `Frame::planes` reads with the new linesizes.
+    pub unsafe fn set_LineSize(&mut self, linesize: &[usize; 4]) {
         self.frame.Linesize = [
             linesize[0] as i32,
             linesize[1] as i32,
@@ -251,3 +826,703 @@ impl Frame {
         ];
     }
 }
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlaneLayout {
+    pub width: usize,
+    pub height: usize,
+    pub linesize: usize,
+    pub pixel_format: PixelFormat,
+}
+
+/// Borrowed snapshot of a decoded frame, valid until the next frame is
+/// decoded from the same `VideoSource`.
+#[derive(Clone, Copy, Debug)]
+pub struct FrameView<'a> {
+    pub planes: [Option<(&'a [u8], PlaneLayout)>; PLANES_COUNT],
+    pub width: usize,
+    pub height: usize,
+    pub pixel_format: PixelFormat,
+}
+
+impl Frame {
+    pub fn plane_layout(&self, plane: usize) -> Option<PlaneLayout> {
+        let (width, height) = self.plane_dimensions(plane)?;
+        let linesize = self.frame.Linesize[plane];
+        if self.frame.Data[plane].is_null() || linesize <= 0 {
+            return None;
+        }
+
+        Some(PlaneLayout {
+            width,
+            height,
+            linesize: linesize as usize,
+            pixel_format: PixelFormat::new(self.frame.ConvertedPixelFormat),
+        })
+    }
+
+    pub fn view(&self) -> FrameView<'_> {
+        let planes = self.planes();
+        let resolution = self.get_frame_resolution();
+        FrameView {
+            planes: std::array::from_fn(|i| {
+                planes[i].zip(self.plane_layout(i))
+            }),
+            width: resolution.width as usize,
+            height: resolution.height as usize,
+            pixel_format: PixelFormat::new(self.frame.ConvertedPixelFormat),
+        }
+    }
+}
+
+#[cfg(feature = "unsafe-api")]
+impl Frame {
+    /// Raw pointer to the FFMS2-owned data of a plane, for zero-copy uploads.
+    ///
+    /// # Safety
+    ///
+    /// The pointer is only valid until the next call decoding a frame or
+    /// changing the output format on the `VideoSource` that returned this
+    /// frame, and until that source is dropped. FFMS2 reuses the same
+    /// buffers, so reading past that point yields another frame or freed
+    /// memory.
+    pub fn plane_ptr(&self, plane: usize) -> Option<(*const u8, PlaneLayout)> {
+        let layout = self.plane_layout(plane)?;
+        Some((self.frame.Data[plane], layout))
+    }
+}
+
+#[cfg(feature = "image")]
+impl Frame {
+    /// Copies an `RGB24` frame into an `image::RgbImage`, dropping the row
+    /// padding. Returns `None` for any other pixel format, so call
+    /// `set_output_format(&[PixelFormat::RGB24], ...)` on the source first.
+    pub fn to_image(&self) -> Option<image::RgbImage> {
+        let (width, height, pixels) =
+            self.packed_pixels(PixelFormat::RGB24)?;
+        image::RgbImage::from_raw(width, height, pixels)
+    }
+
+    /// Copies an `RGBA` frame into an `image::RgbaImage`, dropping the row
+    /// padding. Returns `None` for any other pixel format.
+    pub fn to_rgba_image(&self) -> Option<image::RgbaImage> {
+        let (width, height, pixels) = self.packed_pixels(PixelFormat::RGBA)?;
+        image::RgbaImage::from_raw(width, height, pixels)
+    }
+
+    fn packed_pixels(
+        &self,
+        pixel_format: PixelFormat,
+    ) -> Option<(u32, u32, Vec<u8>)> {
+        if PixelFormat::new(self.frame.ConvertedPixelFormat) != pixel_format {
+            return None;
+        }
+
+        let data = self.planes()[0]?;
+        let linesize = self.frame.Linesize[0] as usize;
+        let resolution = self.get_frame_resolution();
+        let row_bytes =
+            resolution.width as usize * pixel_format.components() as usize;
+        let pixels = data
+            .chunks(linesize)
+            .flat_map(|row| &row[..row_bytes])
+            .copied()
+            .collect();
+
+        Some((resolution.width as u32, resolution.height as u32, pixels))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(
+        data: &[u8],
+        linesize: usize,
+        width: i32,
+        height: i32,
+        pixel_format: PixelFormat,
+    ) -> Frame {
+        let mut frame = Frame::default();
+        unsafe {
+            frame.set_data([data, &[], &[], &[]]);
+            frame.set_LineSize(&[linesize, 0, 0, 0]);
+        }
+        frame.frame.ScaledWidth = width;
+        frame.frame.ScaledHeight = height;
+        frame.frame.ConvertedPixelFormat = pixel_format.to_pix_fmt();
+        frame
+    }
+
+    #[cfg(feature = "image")]
+    #[test]
+    fn solid_color_image() {
+        // 2x2 red pixels, with 2 bytes of padding at the end of each row
+        let data = [255, 0, 0, 255, 0, 0, 9, 9, 255, 0, 0, 255, 0, 0, 9, 9];
+        let rgb = frame(&data, 8, 2, 2, PixelFormat::RGB24);
+        let image = rgb.to_image().unwrap();
+        assert_eq!(image.dimensions(), (2, 2));
+        assert!(image.pixels().all(|pixel| pixel.0 == [255, 0, 0]));
+        assert!(rgb.to_rgba_image().is_none());
+
+        let gray = frame(&data, 8, 2, 2, PixelFormat::GRAY8);
+        assert!(gray.to_image().is_none());
+    }
+
+    #[test]
+    fn pixel_format_names() {
+        let err = Frame::pixel_format("not_a_real_format").unwrap_err();
+        assert_eq!(err.sub_type(), Errors::ERROR_INVALID_ARGUMENT);
+        assert!(Frame::pixel_format("rgb\0").is_err());
+        assert_eq!(Frame::pixel_format("rgb24").unwrap(), PixelFormat::RGB24);
+        assert_eq!(Frame::pixel_format("").unwrap(), PixelFormat::NONE);
+
+        // Names are case sensitive, as in FFmpeg
+        let err = Frame::pixel_format("RGB24").unwrap_err();
+        assert_eq!(err.sub_type(), Errors::ERROR_INVALID_ARGUMENT);
+        assert!(Frame::pixel_format(" rgb24").is_err());
+
+        // Known to FFmpeg but not described by `PixelFormat`
+        let err = Frame::pixel_format("yuv420p9le").unwrap_err();
+        assert_eq!(err.sub_type(), Errors::ERROR_UNSUPPORTED);
+        assert_eq!(Frame::pixel_format("gray").unwrap(), PixelFormat::GRAY8);
+    }
+
+    #[test]
+    fn hdr_transfers() {
+        assert_eq!(
+            TransferCharacteristics::new(16),
+            TransferCharacteristics::SMPTE2084
+        );
+        assert_eq!(
+            TransferCharacteristics::new(18),
+            TransferCharacteristics::ARIB_STD_B67
+        );
+        assert!(TransferCharacteristics::new(16).is_hdr());
+        assert!(!TransferCharacteristics::new(1).is_hdr());
+        assert_eq!(
+            TransferCharacteristics::new(3),
+            TransferCharacteristics::UNSPECIFIED
+        );
+        assert_eq!(ColorPrimaries::new(9), ColorPrimaries::BT2020);
+        assert_eq!(ColorPrimaries::BT2020.value(), 9);
+
+        let mut c_frame = *Frame::default();
+        c_frame.ColorPrimaries = 9;
+        c_frame.TransferCharateristics = 16;
+        c_frame.ColorRange = 1;
+        let frame = Frame::create_frame(&c_frame);
+        assert_eq!(frame.color_primaries(), ColorPrimaries::BT2020);
+        assert!(frame.transfer_characteristics().is_hdr());
+        assert_eq!(frame.color_range(), ColorRanges::CR_MPEG);
+    }
+
+    #[test]
+    fn timecodes() {
+        let mut time_base = TrackTimeBase::default();
+        let ffms_time_base: &mut FFMS_TrackTimeBase = &mut time_base;
+        ffms_time_base.Num = 1000;
+        ffms_time_base.Den = 90000;
+
+        let frame_info = FrameInfo::create_struct(&FrameInfo::default());
+        assert_eq!(frame_info.to_timecode(&time_base), "00:00:00.000");
+
+        // 1 hour, 2 minutes, 3.456 seconds in 90 kHz ticks
+        let mut ffms_frame_info = *FrameInfo::default();
+        ffms_frame_info.PTS = 3_723_456 * 90;
+        let frame_info = FrameInfo::create_struct(&ffms_frame_info);
+        assert_eq!(frame_info.to_timecode(&time_base), "01:02:03.456");
+    }
+
+    #[test]
+    fn timestamp_seconds() {
+        let mut time_base = TrackTimeBase::default();
+        let ffms_time_base: &mut FFMS_TrackTimeBase = &mut time_base;
+        ffms_time_base.Num = 1000;
+        ffms_time_base.Den = 30000;
+        let mut frame_info = *FrameInfo::default();
+        frame_info.PTS = 3003;
+        let frame_info = FrameInfo::create_struct(&frame_info);
+        assert!(
+            (frame_info.timestamp_seconds(&time_base) - 0.1001).abs() < 1e-9
+        );
+    }
+
+    #[test]
+    fn create_frame_without_metadata() {
+        let mut c_frame = *Frame::default();
+        c_frame.MasteringDisplayPrimariesX = [0.68, 0.265, 0.15];
+        c_frame.MasteringDisplayPrimariesY = [0.32, 0.69, 0.06];
+        c_frame.MasteringDisplayMaxLuminance = 1000.0;
+        c_frame.ContentLightLevelMax = 1000;
+
+        let frame = Frame::create_frame(&c_frame);
+        assert_eq!(frame.MasteringDisplayPrimariesX, [0.0; 3]);
+        assert_eq!(frame.MasteringDisplayPrimariesY, [0.0; 3]);
+        assert_eq!(frame.MasteringDisplayMaxLuminance, 0.0);
+        assert_eq!(frame.ContentLightLevelMax, 0);
+    }
+
+    #[test]
+    fn owned_frame_copies_planes() {
+        let luma: Vec<u8> = (0..8 * 3).collect();
+        let chroma: Vec<u8> = (100..104).collect();
+        let mut frame = frame(&luma, 8, 8, 3, PixelFormat::YUV420P);
+        unsafe {
+            frame.set_data([&luma, &chroma, &chroma, &[]]);
+            frame.set_LineSize(&[8, 2, 2, 0]);
+        }
+
+        let owned = OwnedFrame::new(&frame);
+        assert_ne!(owned.Data[0], frame.Data[0]);
+        assert!(owned.Data[3].is_null());
+
+        let luma_copy = unsafe { slice::from_raw_parts(owned.Data[0], 24) };
+        let chroma_copy = unsafe { slice::from_raw_parts(owned.Data[1], 4) };
+        assert_eq!(luma_copy, luma);
+        assert_eq!(chroma_copy, chroma);
+    }
+
+    #[cfg(feature = "unsafe-api")]
+    #[test]
+    fn plane_ptr() {
+        let luma = [16u8; 8 * 2];
+        let frame = frame(&luma, 8, 8, 2, PixelFormat::GRAY8);
+
+        let (data, layout) = frame.plane_ptr(0).unwrap();
+        assert_eq!(data, luma.as_ptr());
+        assert_eq!((layout.width, layout.height, layout.linesize), (8, 2, 8));
+        assert!(frame.plane_ptr(1).is_none());
+    }
+
+    #[test]
+    fn resizer_ratings() {
+        let fast = Resizers::RESIZER_FAST_BILINEAR;
+        let lanczos = Resizers::RESIZER_LANCZOS;
+        assert!(fast.speed() > lanczos.speed());
+        assert!(lanczos.quality() > fast.quality());
+    }
+
+    #[test]
+    fn scaling_directions() {
+        let source = (1920, 1080);
+        assert_eq!(
+            ScalingDirection::new(source, (3840, 2160)),
+            ScalingDirection::Up
+        );
+        assert_eq!(
+            ScalingDirection::new(source, (1280, 720)),
+            ScalingDirection::Down
+        );
+        assert_eq!(
+            ScalingDirection::new(source, (1920, 1080)),
+            ScalingDirection::None
+        );
+        assert_eq!(
+            ScalingDirection::new(source, (1920, 720)),
+            ScalingDirection::Down
+        );
+        assert_eq!(
+            ScalingDirection::new(source, (2560, 720)),
+            ScalingDirection::Mixed
+        );
+    }
+
+    #[test]
+    fn luma() {
+        let data: Vec<u8> = (0..6 * 2).collect();
+        let yuv420 = frame(&data, 6, 4, 2, PixelFormat::YUV420P);
+        assert_eq!(yuv420.luma().unwrap(), [0, 1, 2, 3, 6, 7, 8, 9]);
+
+        let yuv444 = frame(&data, 6, 6, 2, PixelFormat::YUV444P);
+        assert_eq!(yuv444.luma().unwrap(), data);
+
+        let yuyv = frame(&data, 6, 3, 2, PixelFormat::YUYV422);
+        assert_eq!(yuyv.luma().unwrap(), [0, 2, 4, 6, 8, 10]);
+
+        let rgb = frame(&data, 6, 2, 2, PixelFormat::RGB24);
+        assert!(rgb.luma().is_none());
+    }
+
+    #[test]
+    fn luma_padding() {
+        // 3 pixels wide UYVY rows hold 2 groups, padded to 10 bytes
+        let data = [
+            128, 1, 128, 2, 128, 3, 128, 0xff, 0xff, 0xff, //
+            128, 4, 128, 5, 128, 6, 128, 0xff, 0xff, 0xff,
+        ];
+        let uyvy = frame(&data, 10, 3, 2, PixelFormat::UYVY422);
+        assert_eq!(uyvy.luma().unwrap(), [1, 2, 3, 4, 5, 6]);
+
+        let gray = frame(&data, 10, 7, 2, PixelFormat::GRAY8);
+        assert_eq!(gray.luma().unwrap(), [&data[..7], &data[10..17]].concat());
+    }
+
+    #[test]
+    fn nearest_preview() {
+        // 200x100 RGB24 frame whose red channel is the column / 2 and green
+        // channel the row
+        let data: Vec<u8> = (0..100u8)
+            .flat_map(|y| (0..200u8).flat_map(move |x| [x / 2, y, 0]))
+            .collect();
+        let rgb = frame(&data, 600, 200, 100, PixelFormat::RGB24);
+
+        assert_eq!(rgb.downscaled_size(64, 64), (64, 32));
+        let preview = rgb.downscale_nearest(64, 64).unwrap();
+        assert_eq!(preview.len(), 64 * 32 * 3);
+        // Last pixel samples column 196 and row 96
+        assert_eq!(preview[preview.len() - 3..], [98, 96, 0]);
+
+        assert_eq!(rgb.downscaled_size(400, 400), (200, 100));
+        let gray = frame(&data, 200, 200, 100, PixelFormat::GRAY8);
+        assert!(gray.downscale_nearest(64, 64).is_none());
+    }
+
+    #[test]
+    fn nearest_preview_padding() {
+        // 3x3 BGRA frame padded to 16 bytes per row, the blue channel is the
+        // row and the green channel the column
+        let data: Vec<u8> = (0..3u8)
+            .flat_map(|y| {
+                (0..3u8).flat_map(move |x| [y, x, 0, 255]).chain([7; 4])
+            })
+            .collect();
+        let bgra = frame(&data, 16, 3, 3, PixelFormat::BGRA);
+
+        // Odd sizes keep rounding down to the source pixels
+        assert_eq!(bgra.downscaled_size(2, 2), (2, 2));
+        assert_eq!(
+            bgra.downscale_nearest(2, 2).unwrap(),
+            [0, 0, 0, 255, 0, 1, 0, 255, 1, 0, 0, 255, 1, 1, 0, 255]
+        );
+        assert_eq!(bgra.downscale_nearest(8, 8).unwrap().len(), 3 * 3 * 4);
+        assert_eq!(bgra.downscale_nearest(0, 0), Some(vec![]));
+    }
+
+    #[test]
+    fn black_and_uniform() {
+        let black = [16, 17, 16, 18, 0, 0, 16, 16, 17, 16, 0, 0];
+        let black = frame(&black, 6, 4, 2, PixelFormat::GRAY8);
+        assert!(black.is_black());
+        assert!(black.is_uniform(2));
+        assert!(!black.is_uniform(1));
+
+        let data: Vec<u8> = (0..6 * 2).map(|i| i * 20).collect();
+        let gradient = frame(&data, 6, 6, 2, PixelFormat::GRAY8);
+        assert!(!gradient.is_black());
+        assert!(!gradient.is_uniform(16));
+
+        let rgb = frame(&[0; 12], 6, 2, 2, PixelFormat::RGB24);
+        assert!(!rgb.is_black());
+    }
+
+    #[test]
+    fn black_and_uniform_bounds() {
+        // 3x2 black frame whose row padding is white
+        let data = [16, 16, 17, 255, 16, 18, 16, 255];
+        let padded = frame(&data, 4, 3, 2, PixelFormat::GRAY8);
+        assert!(padded.is_black());
+        assert!(padded.is_uniform(2));
+
+        // Odd width YUYV, the chroma bytes are not luma
+        let data = [16, 128, 16, 128, 16, 128, 0, 0];
+        let yuyv = frame(&data, 8, 3, 1, PixelFormat::YUYV422);
+        assert!(yuyv.is_black());
+        assert!(yuyv.is_uniform(0));
+
+        let short = frame(&data, 2, 3, 2, PixelFormat::GRAY8);
+        assert!(!short.is_black());
+        assert!(!short.is_uniform(255));
+
+        let empty = frame(&data, 4, 0, 0, PixelFormat::GRAY8);
+        assert!(!empty.is_black());
+        assert!(!empty.is_uniform(255));
+    }
+
+    #[test]
+    fn packed_yuv_to_planar() {
+        let yuyv = [16, 128, 17, 129, 18, 130, 19, 131];
+        let packed = frame(&yuyv, 8, 4, 1, PixelFormat::YUYV422);
+        let (y, u, v) = packed.packed_yuv_to_planar().unwrap();
+        assert_eq!(y, [16, 17, 18, 19]);
+        assert_eq!(u, [128, 130]);
+        assert_eq!(v, [129, 131]);
+
+        let uyvy = [128, 16, 129, 17, 130, 18, 131, 19];
+        let packed = frame(&uyvy, 8, 3, 1, PixelFormat::UYVY422);
+        let (y, u, v) = packed.packed_yuv_to_planar().unwrap();
+        assert_eq!(y, [16, 17, 18]);
+        assert_eq!(u, [128, 130]);
+        assert_eq!(v, [129, 131]);
+    }
+
+    #[test]
+    fn packed_yuv_padding() {
+        // 3x2 YUYV frame, the rows of 2 groups are padded to 10 bytes
+        let data = [
+            1, 100, 2, 101, 3, 102, 0xff, 103, 0xff, 0xff, //
+            4, 104, 5, 105, 6, 106, 0xff, 107, 0xff, 0xff,
+        ];
+        let padded = frame(&data, 10, 3, 2, PixelFormat::YUYV422);
+        let (y, u, v) = padded.packed_yuv_to_planar().unwrap();
+        assert_eq!(y, [1, 2, 3, 4, 5, 6]);
+        assert_eq!(u, [100, 102, 104, 106]);
+        assert_eq!(v, [101, 103, 105, 107]);
+    }
+
+    #[test]
+    fn planes() {
+        let luma = [16u8; 8 * 4];
+        let chroma = [128u8; 4 * 2];
+        let mut yuv420 = frame(&luma, 8, 6, 4, PixelFormat::YUV420P);
+        unsafe {
+            yuv420.set_data([&luma, &chroma, &chroma, &[]]);
+            yuv420.set_LineSize(&[8, 4, 4, 0]);
+        }
+
+        let planes = yuv420.planes();
+        assert_eq!(planes[0].unwrap().len(), 8 * 4);
+        assert_eq!(planes[1].unwrap().len(), 4 * 2);
+        assert_eq!(planes[2].unwrap(), chroma);
+        assert!(planes[3].is_none());
+    }
+
+    #[test]
+    fn planes_of_undescribed_formats() {
+        let data = [0u8; 4 * 4];
+        let id = Frame::GetPixFmt("yuv420p9le");
+        let mut yuv420p9 = frame(&data, 4, 2, 2, PixelFormat::YUV420P);
+        yuv420p9.frame.ConvertedPixelFormat = id;
+        unsafe {
+            yuv420p9.set_data([&data, &data, &data, &[]]);
+            yuv420p9.set_LineSize(&[4, 2, 2, 0]);
+        }
+
+        assert_eq!(PixelFormat::new(id), PixelFormat::NONE);
+        assert_eq!(yuv420p9.planes(), [None; 4]);
+        assert_eq!(yuv420p9.plane_dimensions(1), None);
+        assert!(OwnedFrame::new(&yuv420p9).Data[0].is_null());
+    }
+
+    #[test]
+    fn pal8_palette() {
+        // 300 rows of indices, taller than the 256 rows of a palette seen
+        // as 4 bytes long lines
+        let indices = [0u8; 2 * 300];
+        let palette = [255u8; PALETTE_SIZE];
+        let mut pal8 = frame(&indices, 2, 2, 300, PixelFormat::PAL8);
+        unsafe {
+            pal8.set_data([&indices, &palette, &[], &[]]);
+            pal8.set_LineSize(&[2, 4, 0, 0]);
+        }
+
+        let planes = pal8.planes();
+        assert_eq!(planes[0].unwrap().len(), 2 * 300);
+        assert_eq!(planes[1].unwrap().len(), PALETTE_SIZE);
+        assert_eq!(pal8.plane_dimensions(1), Some((256, 1)));
+        assert_eq!(pal8.plane_dimensions(2), None);
+    }
+
+    #[test]
+    fn full_height_planes() {
+        let luma = [16u8; 4 * 4];
+        let chroma = [128u8; 2 * 2];
+        let mut yuva420 = frame(&luma, 4, 4, 4, PixelFormat::YUVA420P);
+        unsafe {
+            yuva420.set_data([&luma, &chroma, &chroma, &luma]);
+            yuva420.set_LineSize(&[4, 2, 2, 4]);
+        }
+        let lengths = yuva420.planes().map(|plane| plane.unwrap().len());
+        assert_eq!(lengths, [16, 4, 4, 16]);
+
+        let mut gbrp = frame(&luma, 4, 4, 4, PixelFormat::GBRP);
+        unsafe {
+            gbrp.set_data([&luma, &luma, &luma, &[]]);
+            gbrp.set_LineSize(&[4, 4, 4, 0]);
+        }
+        assert_eq!(gbrp.plane_dimensions(2), Some((4, 4)));
+        assert_eq!(gbrp.planes()[2].unwrap().len(), 16);
+        assert!(gbrp.planes()[3].is_none());
+    }
+
+    #[test]
+    fn yuv420p_view() {
+        let luma = [16u8; 8 * 4];
+        let chroma = [128u8; 4 * 2];
+        let mut yuv420 = frame(&luma, 8, 6, 4, PixelFormat::YUV420P);
+        unsafe {
+            yuv420.set_data([&luma, &chroma, &chroma, &[]]);
+            yuv420.set_LineSize(&[8, 4, 4, 0]);
+        }
+
+        let view = yuv420.view();
+        assert_eq!((view.width, view.height), (6, 4));
+        assert_eq!(view.pixel_format, PixelFormat::YUV420P);
+
+        let layouts: Vec<_> = view
+            .planes
+            .iter()
+            .flatten()
+            .map(|(data, layout)| (data.len(), layout.width, layout.height))
+            .collect();
+        assert_eq!(layouts, [(32, 6, 4), (8, 3, 2), (8, 3, 2)]);
+    }
+
+    #[test]
+    fn x2rgb10_components() {
+        // R = 1023, G = 512, B = 1 with both padding bits set, then black
+        let word: u32 = (0b11 << 30) | (1023 << 20) | (512 << 10) | 1;
+        let mut data = word.to_le_bytes().to_vec();
+        data.extend([0; 4]);
+
+        let rgb = frame(&data, 8, 2, 1, PixelFormat::X2RGB10LE);
+        assert_eq!(rgb.x2rgb10_to_u16_rgb().unwrap(), [1023, 512, 1, 0, 0, 0]);
+
+        let bgr = frame(&data, 8, 2, 1, PixelFormat::X2BGR10LE);
+        assert_eq!(bgr.x2rgb10_to_u16_rgb().unwrap(), [1, 512, 1023, 0, 0, 0]);
+
+        let rgba = frame(&data, 8, 2, 1, PixelFormat::RGBA);
+        assert!(rgba.x2rgb10_to_u16_rgb().is_none());
+    }
+
+    #[test]
+    fn x2rgb10_padding() {
+        // 1x2 frame, each row padded from 4 to 8 bytes with set bits
+        let pixel = |r: u32, g: u32, b: u32| (r << 20) | (g << 10) | b;
+        let data: Vec<u8> =
+            [pixel(1, 2, 3), u32::MAX, pixel(4, 5, 6), u32::MAX]
+                .iter()
+                .flat_map(|word| word.to_le_bytes())
+                .collect();
+        let padded = frame(&data, 8, 1, 2, PixelFormat::X2RGB10LE);
+        assert_eq!(padded.x2rgb10_to_u16_rgb().unwrap(), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn p010_planes() {
+        let luma: Vec<u8> = [100u16, 200, 300, 1023]
+            .iter()
+            .flat_map(|sample| (sample << 6).to_le_bytes())
+            .collect();
+        let chroma: Vec<u8> = [512u16, 64]
+            .iter()
+            .flat_map(|sample| (sample << 6).to_le_bytes())
+            .collect();
+        let mut frame = frame(&luma, 4, 2, 2, PixelFormat::P010LE);
+        unsafe {
+            frame.set_data([&luma, &chroma, &[], &[]]);
+            frame.set_LineSize(&[4, 4, 0, 0]);
+        }
+
+        assert_eq!(frame.plane_dimensions(0), Some((2, 2)));
+        assert_eq!(frame.plane_dimensions(1), Some((1, 1)));
+        assert_eq!(frame.plane_dimensions(2), None);
+
+        let [y, u, v] = frame.p010_to_u16_planes().unwrap();
+        assert_eq!(y, [100, 200, 300, 1023]);
+        assert_eq!(u, [512]);
+        assert_eq!(v, [64]);
+    }
+
+    #[test]
+    fn p010_padding() {
+        // 3x1 frame, luma rows padded from 6 to 8 bytes, chroma 2 pairs
+        let luma: Vec<u8> = [1u16, 2, 3, 0xffff]
+            .iter()
+            .flat_map(|sample| (sample << 4).to_le_bytes())
+            .collect();
+        let chroma: Vec<u8> = [10u16, 20, 30, 40]
+            .iter()
+            .flat_map(|sample| (sample << 4).to_le_bytes())
+            .collect();
+        let mut padded = frame(&luma, 8, 3, 1, PixelFormat::P012LE);
+        unsafe {
+            padded.set_data([&luma, &chroma, &[], &[]]);
+            padded.set_LineSize(&[8, 8, 0, 0]);
+        }
+
+        let [y, u, v] = padded.p010_to_u16_planes().unwrap();
+        assert_eq!(y, [1, 2, 3]);
+        assert_eq!(u, [10, 30]);
+        assert_eq!(v, [20, 40]);
+    }
+
+    #[test]
+    fn unpack_mono() {
+        let data = [0b1010_0000, 0b1100_0000, 0b0000_0000, 0b0100_0000];
+
+        let black = frame(&data, 2, 10, 2, PixelFormat::MONOBLACK);
+        assert_eq!(
+            black.unpack_mono().unwrap(),
+            [
+                255, 0, 255, 0, 0, 0, 0, 0, 255, 255, //
+                0, 0, 0, 0, 0, 0, 0, 0, 0, 255,
+            ]
+        );
+
+        let white = frame(&data, 2, 10, 2, PixelFormat::MONOWHITE);
+        assert_eq!(white.unpack_mono().unwrap()[..4], [0, 255, 0, 255]);
+
+        let rgb = frame(&data, 2, 10, 2, PixelFormat::RGB24);
+        assert_eq!(rgb.unpack_mono(), None);
+    }
+
+    #[test]
+    fn unpack_mono_padding() {
+        // 3 pixels wide rows padded to 2 bytes, the padding bits are set
+        let data = [0b1011_1111, 0xff, 0b0101_1111, 0xff];
+        let padded = frame(&data, 2, 3, 2, PixelFormat::MONOBLACK);
+        assert_eq!(padded.unpack_mono().unwrap(), [255, 0, 255, 0, 255, 0]);
+    }
+
+    #[test]
+    fn accessors_check_plane_bounds() {
+        type Accessor = fn(&Frame) -> bool;
+        // Pixel format and width of 2 rows high frames, with the bytes a row
+        // needs in every plane the accessor reads
+        let cases: [(PixelFormat, i32, usize, &str, Accessor); 8] = [
+            (PixelFormat::GRAY8, 3, 3, "luma", |f| f.luma().is_some()),
+            (PixelFormat::YUYV422, 3, 6, "luma", |f| f.luma().is_some()),
+            (PixelFormat::UYVY422, 5, 12, "packed_yuv_to_planar", |f| {
+                f.packed_yuv_to_planar().is_some()
+            }),
+            (PixelFormat::BGRA, 3, 12, "downscale_nearest", |f| {
+                f.downscale_nearest(2, 2).is_some()
+            }),
+            (PixelFormat::X2RGB10LE, 3, 12, "x2rgb10_to_u16_rgb", |f| {
+                f.x2rgb10_to_u16_rgb().is_some()
+            }),
+            (PixelFormat::X2BGR10LE, 1, 4, "x2rgb10_to_u16_rgb", |f| {
+                f.x2rgb10_to_u16_rgb().is_some()
+            }),
+            // The chroma plane holds 2 pairs of 2 bytes samples
+            (PixelFormat::P012LE, 3, 8, "p010_to_u16_planes", |f| {
+                f.p010_to_u16_planes().is_some()
+            }),
+            (PixelFormat::MONOBLACK, 10, 2, "unpack_mono", |f| {
+                f.unpack_mono().is_some()
+            }),
+        ];
+
+        for (pixel_format, width, row, accessor, read) in cases {
+            let data = vec![0u8; row * 2];
+            let with_linesize = |linesize: usize| {
+                let mut frame = frame(&data, linesize, width, 2, pixel_format);
+                frame.frame.Data = [data.as_ptr(); 4];
+                frame.frame.Linesize = [linesize as i32; 4];
+                frame
+            };
+            let name = format!("{} of {:?}", accessor, pixel_format);
+
+            assert!(read(&with_linesize(row)), "{}", name);
+            assert!(!read(&with_linesize(row - 1)), "short {}", name);
+            assert!(!read(&with_linesize(0)), "no linesize {}", name);
+            for plane in 0..pixel_format.planes_count() {
+                let mut missing = with_linesize(row);
+                missing.frame.Data[plane] = ptr::null();
+                assert!(!read(&missing), "missing plane {} {}", plane, name);
+            }
+        }
+    }
+}
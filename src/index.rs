@@ -3,25 +3,202 @@ use crate::*;
 
 use ffms2_sys::*;
 
+use std::cell::RefCell;
+use std::collections::hash_map::RandomState;
 use std::default::Default;
-use std::ffi::c_char;
+use std::env;
+use std::ffi::CStr;
 use std::ffi::CString;
+use std::fs;
+use std::hash::{BuildHasher, Hasher};
+use std::io;
+use std::io::Write;
+use std::iter;
 use std::mem;
-use std::os::raw::c_void;
+use std::os::raw::{c_char, c_void};
 use std::panic;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process;
+use std::ptr;
+use std::slice;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
 
+fn all_indexed(tracks: impl IntoIterator<Item = (TrackType, usize)>) -> bool {
+    tracks
+        .into_iter()
+        .all(|(track_type, frames)| !track_type.is_indexable() || frames > 0)
+}
+
+// Copies `size` bytes out of a buffer allocated by FFMS2, then releases it
+// through `free`
+unsafe fn take_buffer<F>(mut buffer: *mut u8, size: usize, free: F) -> Vec<u8>
+where
+    F: FnOnce(*mut *mut u8),
+{
+    let bytes = if buffer.is_null() {
+        Vec::new()
+    } else {
+        slice::from_raw_parts(buffer, size).to_vec()
+    };
+    free(&mut buffer);
+    bytes
+}
+
+// Copies a string owned by FFMS2, which may also be null for an unknown
+// codec, without taking ownership of it
+unsafe fn borrowed_string(c_ptr: *const c_char) -> String {
+    if c_ptr.is_null() {
+        String::new()
+    } else {
+        CStr::from_ptr(c_ptr).to_string_lossy().into_owned()
+    }
+}
+
+// FFMS2 takes UTF-8 paths without interior NUL bytes
+pub(crate) fn path_to_cstring(
+    path: &Path,
+    error_type: Errors,
+) -> Result<CString, Error> {
+    path.to_str()
+        .and_then(|path| CString::new(path).ok())
+        .ok_or_else(|| {
+            Error::new(
+                error_type,
+                Errors::ERROR_INVALID_ARGUMENT,
+                &format!("{} is not a valid path", path.display()),
+            )
+        })
+}
+
+fn count_or_zero(count: i32) -> usize {
+    usize::try_from(count).unwrap_or(0)
+}
+
+fn is_current(belongs_to_file: Result<(), Error>) -> Result<bool, Error> {
+    match belongs_to_file {
+        Ok(()) => Ok(true),
+        Err(error) if error.sub_type() == Errors::ERROR_FILE_MISMATCH => {
+            Ok(false)
+        }
+        Err(error) => Err(error),
+    }
+}
+
+fn checked_index<T, F>(index: Result<T, Error>, belongs: F) -> Result<T, Error>
+where
+    F: FnOnce(&T) -> Result<(), Error>,
+{
+    let index = index?;
+    belongs(&index)?;
+    Ok(index)
+}
+
+// A cached index which cannot be read or is stale is created again
+fn cached_or_created<T, R, C>(
+    cached: bool,
+    read: R,
+    create: C,
+) -> Result<T, Error>
+where
+    R: FnOnce() -> Result<T, Error>,
+    C: FnOnce() -> Result<T, Error>,
+{
+    match cached.then(read) {
+        Some(Ok(index)) => Ok(index),
+        _ => create(),
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum TrackSelector {
+    Track(usize),
+    Type(TrackType),
+}
+
+// The last setting matching a track wins, FFMS2 only indexes video tracks by
+// default
+fn track_enabled(
+    settings: &[(TrackSelector, bool)],
+    track: usize,
+    track_type: TrackType,
+) -> bool {
+    settings
+        .iter()
+        .rev()
+        .find(|(selector, _)| match selector {
+            TrackSelector::Track(n) => *n == track,
+            TrackSelector::Type(t) => *t == track_type,
+        })
+        .map_or(track_type == TrackType::TYPE_VIDEO, |(_, enabled)| *enabled)
+}
+
+fn check_track(track: usize, tracks_count: usize) -> Result<(), Error> {
+    if track < tracks_count {
+        Ok(())
+    } else {
+        Err(Error::new(
+            Errors::ERROR_INDEXING,
+            Errors::ERROR_INVALID_ARGUMENT,
+            &format!(
+                "Track {} does not exist, the file has {} tracks",
+                track, tracks_count
+            ),
+        ))
+    }
+}
+
+fn check_indexable(track_type: TrackType) -> Result<(), Error> {
+    if track_type.is_indexable() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            Errors::ERROR_INDEXING,
+            Errors::ERROR_UNSUPPORTED,
+            &format!("{:?} tracks cannot be indexed", track_type),
+        ))
+    }
+}
+
+// Tracks as (type, enabled, indexed frames)
+fn empty_track_warnings(
+    tracks: impl IntoIterator<Item = (TrackType, bool, usize)>,
+) -> Vec<String> {
+    tracks
+        .into_iter()
+        .enumerate()
+        .filter(|(_, (track_type, enabled, frames))| {
+            track_type.is_indexable() && *enabled && *frames == 0
+        })
+        .map(|(n, (track_type, _, _))| {
+            format!(
+                "Track {} ({:?}) could not be indexed and was cleared",
+                n, track_type
+            )
+        })
+        .collect()
+}
+
+/// Tracks of a media file as indexed by FFMS2. Container level data such as
+/// chapters is not part of the FFMS2 API, so it cannot be read from here.
 pub struct Index {
     index: *mut FFMS_Index,
-    buffer: Vec<u8>,
+    warnings: Vec<String>,
 }
 
 unsafe impl Send for Index {}
 
 impl Index {
+    /// Same as `Index::from_file`.
     pub fn new(IndexFile: &Path) -> Result<Self, Error> {
-        let source = CString::new(IndexFile.to_str().unwrap()).unwrap();
+        Index::from_file(IndexFile)
+    }
+
+    /// Reads an index written by `write_to_file`. A truncated or corrupt
+    /// index file is reported as an error for which
+    /// `Error::is_corrupt_index` is true.
+    pub fn from_file(index_file: &Path) -> Result<Self, Error> {
+        let source = path_to_cstring(index_file, Errors::ERROR_INDEX)?;
         let mut error: Error = Default::default();
         let index =
             unsafe { FFMS_ReadIndex(source.as_ptr(), error.as_mut_ptr()) };
@@ -31,11 +208,45 @@ impl Index {
         } else {
             Ok(Index {
                 index,
-                buffer: Vec::new(),
+                warnings: Vec::new(),
             })
         }
     }
 
+    /// Reads an index and checks that it was created from `source_file`.
+    ///
+    /// A stale index is reported as an `ERROR_INDEX` error whose sub type is
+    /// `ERROR_FILE_MISMATCH`, so callers know they have to re-index.
+    pub fn new_with_fallback(
+        index_file: &Path,
+        source_file: &Path,
+    ) -> Result<Self, Error> {
+        checked_index(Index::from_file(index_file), |index| {
+            index.belongs_to_file(source_file)
+        })
+    }
+
+    /// Reads `index_file` if it exists and belongs to `source_file`, otherwise
+    /// indexes all the tracks of `source_file` and writes the result to
+    /// `index_file`.
+    pub fn open_or_create(
+        index_file: &Path,
+        source_file: &Path,
+    ) -> Result<Self, Error> {
+        cached_or_created(
+            index_file.is_file(),
+            || Index::new_with_fallback(index_file, source_file),
+            || {
+                let indexer = Indexer::new(source_file)?;
+                indexer.enable_track_type(TrackType::TYPE_AUDIO)?;
+                let index =
+                    indexer.do_indexing(IndexErrorHandling::IEH_ABORT)?;
+                index.write_to_file(index_file)?;
+                Ok(index)
+            },
+        )
+    }
+
     pub fn ErrorHandling(&self) -> IndexErrorHandling {
         let index_error_handling =
             unsafe { FFMS_GetErrorHandling(self.index) };
@@ -67,7 +278,7 @@ impl Index {
         } else {
             Ok(Index {
                 index,
-                buffer: Vec::new(),
+                warnings: Vec::new(),
             })
         }
     }
@@ -91,7 +302,7 @@ impl Index {
     }
 
     pub fn WriteIndex(&self, SourceFile: &Path) -> Result<(), Error> {
-        let source = CString::new(SourceFile.to_str().unwrap()).unwrap();
+        let source = path_to_cstring(SourceFile, Errors::ERROR_INDEX)?;
         let mut error: Error = Default::default();
         let err = unsafe {
             FFMS_WriteIndex(source.as_ptr(), self.index, error.as_mut_ptr())
@@ -104,12 +315,21 @@ impl Index {
         }
     }
 
-    pub fn WriteIndexToBuffer(&mut self) -> Result<&Vec<u8>, Error> {
+    pub fn write_to_file(&self, index_file: &Path) -> Result<(), Error> {
+        self.WriteIndex(index_file)
+    }
+
+    /// Serializes the index, to be read back with `read_from_buffer`. The
+    /// bytes are copied out of the buffer allocated by FFMS2, which is freed
+    /// right away, so the `Index` never holds on to it and has no buffer to
+    /// free later.
+    pub fn WriteIndexToBuffer(&self) -> Result<Vec<u8>, Error> {
         let mut error: Error = Default::default();
+        let mut buffer = ptr::null_mut();
         let mut size = 0;
         let err = unsafe {
             FFMS_WriteIndexToBuffer(
-                &mut self.buffer.as_mut_ptr(),
+                &mut buffer,
                 &mut size,
                 self.index,
                 error.as_mut_ptr(),
@@ -119,7 +339,11 @@ impl Index {
         if err != 0 {
             Err(error)
         } else {
-            Ok(&self.buffer)
+            Ok(unsafe {
+                take_buffer(buffer, size, |buffer| {
+                    FFMS_FreeIndexBuffer(buffer)
+                })
+            })
         }
     }
 
@@ -165,6 +389,64 @@ impl Index {
         unsafe { FFMS_GetNumTracks(self.index) as usize }
     }
 
+    /// Whether every video and audio track has been indexed. FFMS2 never
+    /// indexes other track types, so they are not taken into account.
+    pub fn covers_all_tracks(&self) -> bool {
+        all_indexed((0..self.tracks_count()).map(|n| {
+            let track = Track::TrackFromIndex(self, n);
+            (track.TrackType(), track.NumFrames())
+        }))
+    }
+
+    /// Number of tracks of the file, indexed or not.
+    pub fn tracks_count(&self) -> usize {
+        count_or_zero(unsafe { FFMS_GetNumTracks(self.index) })
+    }
+
+    pub fn read_from_buffer(buffer: &[u8]) -> Result<Self, Error> {
+        Index::ReadIndexFromBuffer(buffer)
+    }
+
+    pub fn belongs_to_file(&self, source_file: &Path) -> Result<(), Error> {
+        self.IndexBelongsToFile(source_file)
+    }
+
+    /// Whether the index still describes `source_file`, in which case there
+    /// is no need to re-index it. The index does not record modification
+    /// times, FFMS2 compares the size and a digest of the content of the
+    /// file instead, so touching a file without changing it keeps its index
+    /// current while any edit invalidates it.
+    pub fn is_current_for(&self, source_file: &Path) -> Result<bool, Error> {
+        is_current(self.belongs_to_file(source_file))
+    }
+
+    pub fn first_track_of_type(
+        &self,
+        track_type: TrackType,
+    ) -> Result<usize, Error> {
+        self.FirstTrackOfType(track_type)
+    }
+
+    pub fn first_indexed_track_of_type(
+        &self,
+        track_type: TrackType,
+    ) -> Result<usize, Error> {
+        self.FirstIndexedTrackOfType(track_type)
+    }
+
+    pub fn error_handling(&self) -> IndexErrorHandling {
+        self.ErrorHandling()
+    }
+
+    /// Tracks which were enabled but ended up empty when indexing with an
+    /// error handling other than `IEH_ABORT`. FFMS2 does not report the
+    /// errors themselves, nor where a track stopped with `IEH_STOP_TRACK`,
+    /// so only cleared tracks are listed. Always empty for indexes read
+    /// from a file or a buffer.
+    pub fn indexing_warnings(&self) -> Vec<String> {
+        self.warnings.clone()
+    }
+
     pub(crate) fn as_mut_ptr(&self) -> *mut FFMS_Index {
         self.index
     }
@@ -173,16 +455,86 @@ impl Index {
 impl Drop for Index {
     fn drop(&mut self) {
         unsafe {
-            if !self.buffer.is_empty() {
-                FFMS_FreeIndexBuffer(&mut self.buffer.as_mut_ptr());
-            }
             FFMS_DestroyIndex(self.index);
         }
     }
 }
 
+// File holding in-memory media, removed when dropped
+struct TempFile {
+    path: PathBuf,
+}
+
+// Names tried before giving up on finding an unused one
+const TEMP_FILE_ATTEMPTS: usize = 16;
+
+impl TempFile {
+    fn new(bytes: &[u8]) -> io::Result<Self> {
+        TempFile::create_in(
+            &env::temp_dir(),
+            iter::repeat_with(temp_file_name),
+            bytes,
+        )
+    }
+
+    // Creates the file under the first name not taken yet, so an existing
+    // file or link is never written through
+    fn create_in<I>(dir: &Path, names: I, bytes: &[u8]) -> io::Result<Self>
+    where
+        I: IntoIterator<Item = String>,
+    {
+        let mut last_error = io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            "No unused temporary file name",
+        );
+        for name in names.into_iter().take(TEMP_FILE_ATTEMPTS) {
+            let path = dir.join(name);
+            match create_private(&path) {
+                Ok(mut file) => {
+                    let temp_file = TempFile { path };
+                    file.write_all(bytes)?;
+                    return Ok(temp_file);
+                }
+                Err(err) if err.kind() == io::ErrorKind::AlreadyExists => {
+                    last_error = err;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+        Err(last_error)
+    }
+}
+
+fn temp_file_name() -> String {
+    static COUNTER: AtomicUsize = AtomicUsize::new(0);
+    let mut hasher = RandomState::new().build_hasher();
+    hasher.write_u32(process::id());
+    hasher.write_usize(COUNTER.fetch_add(1, Ordering::Relaxed));
+    if let Ok(now) = SystemTime::now().duration_since(UNIX_EPOCH) {
+        hasher.write_u128(now.as_nanos());
+    }
+    format!("ffms2-{:016x}", hasher.finish())
+}
+
+// Readable and writable by the owner only on Unix
+fn create_private(path: &Path) -> io::Result<fs::File> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    options.open(path)
+}
+
+impl Drop for TempFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
 pub struct Indexer {
     indexer: *mut FFMS_Indexer,
+    temp_file: Option<TempFile>,
+    settings: RefCell<Vec<(TrackSelector, bool)>>,
 }
 
 unsafe impl Send for Indexer {}
@@ -197,20 +549,44 @@ impl Indexer {
         if indexer.is_null() {
             Err(error)
         } else {
-            Ok(Indexer { indexer })
+            Ok(Indexer {
+                indexer,
+                temp_file: None,
+                settings: RefCell::new(Vec::new()),
+            })
         }
     }
 
+    /// Indexes media held in memory. FFMS2 only opens files by name, so the
+    /// bytes are written to a file in the temporary directory which is
+    /// removed when the indexer is dropped. Video and audio sources have to
+    /// be opened from `source_file` while the indexer is still alive.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let temp_file = TempFile::new(bytes).map_err(|err| {
+            Error::new(
+                Errors::ERROR_INDEXING,
+                Errors::ERROR_FILE_WRITE,
+                &format!("Cannot write a temporary file: {}", err),
+            )
+        })?;
+        let mut indexer = Indexer::new(&temp_file.path)?;
+        indexer.temp_file = Some(temp_file);
+        Ok(indexer)
+    }
+
+    /// Path of the temporary file created by `from_bytes`.
+    pub fn source_file(&self) -> Option<&Path> {
+        self.temp_file.as_ref().map(|file| file.path.as_path())
+    }
+
     pub fn CodecNameI(&self, Track: usize) -> String {
-        let c_ptr = unsafe { FFMS_GetCodecNameI(self.indexer, Track as i32) };
-        let c_str = unsafe { CString::from_raw(c_ptr as *mut c_char) };
-        c_str.to_str().unwrap().to_owned()
+        unsafe {
+            borrowed_string(FFMS_GetCodecNameI(self.indexer, Track as i32))
+        }
     }
 
     pub fn FormatNameI(&self) -> String {
-        let c_ptr = unsafe { FFMS_GetFormatNameI(self.indexer) };
-        let c_str = unsafe { CString::from_raw(c_ptr as *mut c_char) };
-        c_str.to_str().unwrap().to_owned()
+        unsafe { borrowed_string(FFMS_GetFormatNameI(self.indexer)) }
     }
 
     pub fn NumTracksI(&self) -> usize {
@@ -223,6 +599,15 @@ impl Indexer {
         TrackType::from_i32(track_type)
     }
 
+    /// Types of the tracks of the file FFMS2 is able to index, in track
+    /// order. Subtitle, data and attachment tracks are left out.
+    pub fn supported_track_types(&self) -> Vec<TrackType> {
+        (0..self.NumTracksI())
+            .map(|track| self.TrackTypeI(track))
+            .filter(|track_type| track_type.is_indexable())
+            .collect()
+    }
+
     pub fn CancelIndexing(&self) {
         unsafe {
             FFMS_CancelIndexing(self.indexer);
@@ -233,6 +618,17 @@ impl Indexer {
         &self,
         ErrorHandling: IndexErrorHandling,
     ) -> Result<Index, Error> {
+        // The indexer is gone once indexing is done
+        let tracks: Vec<(TrackType, bool)> = {
+            let settings = self.settings.borrow();
+            (0..self.NumTracksI())
+                .map(|n| {
+                    let track_type = self.TrackTypeI(n);
+                    (track_type, track_enabled(&settings, n, track_type))
+                })
+                .collect()
+        };
+
         let mut error: Error = Default::default();
         let handling = IndexErrorHandling::to_idx_errors(ErrorHandling) as i32;
         let index = unsafe {
@@ -242,14 +638,24 @@ impl Indexer {
         if index.is_null() {
             Err(error)
         } else {
-            Ok(Index {
+            let mut index = Index {
                 index,
-                buffer: Vec::new(),
-            })
+                warnings: Vec::new(),
+            };
+            index.warnings = empty_track_warnings(
+                tracks.into_iter().enumerate().map(|(n, (track_type, on))| {
+                    let frames = Track::TrackFromIndex(&index, n).NumFrames();
+                    (track_type, on, frames)
+                }),
+            );
+            Ok(index)
         }
     }
 
     pub fn TrackIndexSettings(&self, Track: usize, Index: usize) {
+        self.settings
+            .borrow_mut()
+            .push((TrackSelector::Track(Track), Index != 0));
         unsafe {
             FFMS_TrackIndexSettings(
                 self.indexer,
@@ -261,6 +667,9 @@ impl Indexer {
     }
 
     pub fn TrackTypeIndexSettings(&self, TrackType: TrackType, Index: usize) {
+        self.settings
+            .borrow_mut()
+            .push((TrackSelector::Type(TrackType), Index != 0));
         unsafe {
             FFMS_TrackTypeIndexSettings(
                 self.indexer,
@@ -271,6 +680,32 @@ impl Indexer {
         }
     }
 
+    /// Indexes the track at position `track` of the file, which is not done
+    /// by default for audio tracks.
+    pub fn enable_track(&self, track: usize) -> Result<(), Error> {
+        check_track(track, self.NumTracksI())?;
+        self.TrackIndexSettings(track, 1);
+        Ok(())
+    }
+
+    /// Indexes every track of type `track_type`. Only video and audio
+    /// tracks can be indexed.
+    pub fn enable_track_type(
+        &self,
+        track_type: TrackType,
+    ) -> Result<(), Error> {
+        check_indexable(track_type)?;
+        self.TrackTypeIndexSettings(track_type, 1);
+        Ok(())
+    }
+
+    pub fn do_indexing(
+        &self,
+        error_handling: IndexErrorHandling,
+    ) -> Result<Index, Error> {
+        self.DoIndexing2(error_handling)
+    }
+
     pub fn ProgressCallback<F>(&self, callback: F, value: &mut usize)
     where
         F: FnMut(usize, usize, Option<&mut usize>) -> usize + 'static,
@@ -322,3 +757,296 @@ impl Indexer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::cell::Cell;
+
+    #[test]
+    fn modified_source_is_not_current() {
+        assert!(is_current(Ok(())).unwrap());
+
+        let mismatch = Error::new(
+            Errors::ERROR_INDEX,
+            Errors::ERROR_FILE_MISMATCH,
+            "Index and source file do not match",
+        );
+        assert!(!is_current(Err(mismatch)).unwrap());
+
+        let missing =
+            Error::new(Errors::ERROR_PARSER, Errors::ERROR_FILE_READ, "");
+        assert!(is_current(Err(missing)).is_err());
+    }
+
+    #[test]
+    fn enabled_tracks_checked() {
+        assert!(check_track(0, 2).is_ok());
+        assert!(check_track(1, 2).is_ok());
+        assert_eq!(
+            check_track(2, 2).unwrap_err().sub_type(),
+            Errors::ERROR_INVALID_ARGUMENT
+        );
+        assert!(check_track(0, 0).is_err());
+
+        assert!(check_indexable(TrackType::TYPE_VIDEO).is_ok());
+        assert!(check_indexable(TrackType::TYPE_AUDIO).is_ok());
+        assert_eq!(
+            check_indexable(TrackType::TYPE_SUBTITLE)
+                .unwrap_err()
+                .sub_type(),
+            Errors::ERROR_UNSUPPORTED
+        );
+    }
+
+    #[test]
+    fn index_buffer_copied_then_freed() {
+        let mut written = *b"FFMSINDEX";
+        let mut frees = Vec::new();
+        let bytes = unsafe {
+            take_buffer(written.as_mut_ptr(), written.len(), |buffer| {
+                frees.push(*buffer);
+                *buffer = ptr::null_mut();
+            })
+        };
+        // FFMS2 may overwrite the buffer once it is freed
+        written.fill(0);
+
+        assert_eq!(bytes, b"FFMSINDEX");
+        assert_eq!(frees.len(), 1);
+
+        let empty = unsafe { take_buffer(ptr::null_mut(), 0, |_| {}) };
+        assert!(empty.is_empty());
+    }
+
+    #[test]
+    fn borrowed_names_stay_owned_by_ffms2() {
+        let name = CString::new("h264").unwrap();
+        let first = unsafe { borrowed_string(name.as_ptr()) };
+        let second = unsafe { borrowed_string(name.as_ptr()) };
+        assert_eq!(first, "h264");
+        assert_eq!(second, "h264");
+
+        // The copies outlive the string they were read from
+        drop(name);
+        assert_eq!(first, second);
+
+        assert_eq!(unsafe { borrowed_string(ptr::null()) }, "");
+    }
+
+    #[test]
+    fn index_paths() {
+        let path = Path::new("cache/video.ffindex");
+        assert_eq!(
+            path_to_cstring(path, Errors::ERROR_INDEX).unwrap().to_str(),
+            Ok("cache/video.ffindex")
+        );
+
+        let nul =
+            path_to_cstring(Path::new("video\0.ffindex"), Errors::ERROR_INDEX)
+                .unwrap_err();
+        assert_eq!(nul.error_type(), Errors::ERROR_INDEX);
+        assert_eq!(nul.sub_type(), Errors::ERROR_INVALID_ARGUMENT);
+
+        let source = Path::new("video\0.mkv");
+        let source_err =
+            path_to_cstring(source, Errors::ERROR_FILE_READ).unwrap_err();
+        assert_eq!(source_err.error_type(), Errors::ERROR_FILE_READ);
+
+        #[cfg(unix)]
+        {
+            use std::ffi::OsStr;
+            use std::os::unix::ffi::OsStrExt;
+
+            let latin1 = Path::new(OsStr::from_bytes(b"vid\xe9o.ffindex"));
+            assert!(path_to_cstring(latin1, Errors::ERROR_INDEX).is_err());
+        }
+    }
+
+    #[test]
+    fn tracks_count_never_wraps() {
+        assert_eq!(count_or_zero(3), 3);
+        assert_eq!(count_or_zero(0), 0);
+        assert_eq!(count_or_zero(-1), 0);
+    }
+
+    #[test]
+    fn stale_index_rejected() {
+        let mismatch = || {
+            Err(Error::new(
+                Errors::ERROR_INDEX,
+                Errors::ERROR_FILE_MISMATCH,
+                "Index and source file do not match",
+            ))
+        };
+        let stale = checked_index(Ok("index"), |_| mismatch());
+        assert_eq!(stale.unwrap_err().sub_type(), Errors::ERROR_FILE_MISMATCH);
+
+        let unreadable = checked_index(
+            Err(Error::new(
+                Errors::ERROR_PARSER,
+                Errors::ERROR_FILE_READ,
+                "",
+            )),
+            |_: &&str| panic!("An unreadable index was checked"),
+        );
+        assert_eq!(
+            unreadable.unwrap_err().sub_type(),
+            Errors::ERROR_FILE_READ
+        );
+
+        assert_eq!(checked_index(Ok("index"), |_| Ok(())).unwrap(), "index");
+    }
+
+    #[test]
+    fn cached_index_reused() {
+        let stale = || {
+            Err(Error::new(
+                Errors::ERROR_INDEX,
+                Errors::ERROR_FILE_MISMATCH,
+                "Index and source file do not match",
+            ))
+        };
+
+        let created = Cell::new(0);
+        let create = || {
+            created.set(created.get() + 1);
+            Ok("created")
+        };
+        assert_eq!(
+            cached_or_created(true, || Ok("cached"), create).unwrap(),
+            "cached"
+        );
+        assert_eq!(created.get(), 0);
+
+        assert_eq!(cached_or_created(true, stale, create).unwrap(), "created");
+        assert_eq!(
+            cached_or_created(
+                false,
+                || -> Result<&str, Error> { panic!("No cached index") },
+                create
+            )
+            .unwrap(),
+            "created"
+        );
+        assert_eq!(created.get(), 2);
+    }
+
+    #[test]
+    #[ignore = "needs a video file in FFMS2_TEST_VIDEO"]
+    fn stale_cached_index_rebuilt() {
+        let video = env::var_os("FFMS2_TEST_VIDEO").unwrap();
+        let source = TempFile::new(&fs::read(video).unwrap()).unwrap();
+        let index_file = TempFile::new(&[]).unwrap();
+        fs::remove_file(&index_file.path).unwrap();
+        let modified = || fs::metadata(&index_file.path)?.modified();
+        let backdate = || {
+            let time = UNIX_EPOCH + std::time::Duration::from_secs(1);
+            let file = fs::File::options().write(true).open(&index_file.path);
+            file.and_then(|file| file.set_modified(time)).map(|_| time)
+        };
+        FFMS2::Init();
+
+        Index::open_or_create(&index_file.path, &source.path).unwrap();
+        assert!(index_file.path.is_file());
+
+        // A current cache is read, not written again
+        let written = backdate().unwrap();
+        Index::open_or_create(&index_file.path, &source.path).unwrap();
+        assert_eq!(modified().unwrap(), written);
+
+        // Trailing bytes change the size and digest of the source
+        let mut file =
+            fs::File::options().append(true).open(&source.path).unwrap();
+        file.write_all(&[0; 4096]).unwrap();
+        let stale = Index::from_file(&index_file.path).unwrap();
+        assert!(!stale.is_current_for(&source.path).unwrap());
+
+        let index =
+            Index::open_or_create(&index_file.path, &source.path).unwrap();
+        assert_ne!(modified().unwrap(), written);
+        assert!(index.is_current_for(&source.path).unwrap());
+        let cached = Index::from_file(&index_file.path).unwrap();
+        assert!(cached.is_current_for(&source.path).unwrap());
+    }
+
+    #[test]
+    fn temp_file_removed_on_drop() {
+        let first = TempFile::new(b"RIFF").unwrap();
+        let second = TempFile::new(b"RIFF").unwrap();
+        assert_ne!(first.path, second.path);
+        assert_eq!(fs::read(&first.path).unwrap(), b"RIFF");
+
+        let path = first.path.clone();
+        drop(first);
+        assert!(!path.exists());
+        assert!(second.path.exists());
+    }
+
+    #[test]
+    fn temp_file_skips_taken_names() {
+        let dir = env::temp_dir();
+        let taken = temp_file_name();
+        let free = temp_file_name();
+        fs::write(dir.join(&taken), b"kept").unwrap();
+
+        let names = [taken.clone(), free.clone()];
+        let temp_file = TempFile::create_in(&dir, names, b"RIFF").unwrap();
+        assert_eq!(temp_file.path, dir.join(&free));
+        assert_eq!(fs::read(&temp_file.path).unwrap(), b"RIFF");
+        assert_eq!(fs::read(dir.join(&taken)).unwrap(), b"kept");
+
+        let all_taken = TempFile::create_in(&dir, [taken.clone()], b"RIFF");
+        assert_eq!(
+            all_taken.err().map(|err| err.kind()),
+            Some(io::ErrorKind::AlreadyExists)
+        );
+        fs::remove_file(dir.join(&taken)).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn temp_file_private_mode() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_file = TempFile::new(b"RIFF").unwrap();
+        let mode = fs::metadata(&temp_file.path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn cleared_audio_track_warning() {
+        let settings = [
+            (TrackSelector::Type(TrackType::TYPE_AUDIO), true),
+            (TrackSelector::Track(2), false),
+        ];
+        assert!(track_enabled(&settings, 0, TrackType::TYPE_VIDEO));
+        assert!(track_enabled(&settings, 1, TrackType::TYPE_AUDIO));
+        assert!(!track_enabled(&settings, 2, TrackType::TYPE_AUDIO));
+        assert!(!track_enabled(&[], 1, TrackType::TYPE_AUDIO));
+
+        let warnings = empty_track_warnings([
+            (TrackType::TYPE_VIDEO, true, 240),
+            (TrackType::TYPE_AUDIO, true, 0),
+            (TrackType::TYPE_AUDIO, false, 0),
+            (TrackType::TYPE_SUBTITLE, true, 0),
+        ]);
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].starts_with("Track 1 (TYPE_AUDIO)"));
+    }
+
+    #[test]
+    fn all_tracks_indexed() {
+        let video_only =
+            [(TrackType::TYPE_VIDEO, 240), (TrackType::TYPE_AUDIO, 0)];
+        assert!(!all_indexed(video_only));
+
+        let full = [
+            (TrackType::TYPE_VIDEO, 240),
+            (TrackType::TYPE_AUDIO, 375),
+            (TrackType::TYPE_SUBTITLE, 0),
+        ];
+        assert!(all_indexed(full));
+    }
+}
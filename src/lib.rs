@@ -8,15 +8,25 @@ mod utility;
 pub mod audio;
 pub mod frame;
 pub mod index;
+pub mod pixel;
 pub mod resample;
+#[cfg(feature = "testutils")]
+pub mod testutils;
 pub mod track;
 pub mod video;
 
+use audio::*;
+use frame::*;
+use index::*;
+use track::*;
+use video::*;
+
 use ffms2_sys::*;
 
 use std::ffi::c_char;
 use std::fmt;
 use std::mem;
+use std::path::Path;
 use std::ptr;
 use std::str;
 
@@ -65,6 +75,23 @@ display!(IndexErrorHandling,
          )
 );
 
+from_str!(IndexErrorHandling, "index error handling",
+          (
+            IEH_ABORT: "abort",
+            IEH_CLEAR_TRACK: "clear_track",
+            IEH_STOP_TRACK: "stop_track",
+            IEH_IGNORE: "ignore",
+          )
+);
+
+#[allow(non_upper_case_globals)]
+impl IndexErrorHandling {
+    pub const Abort: Self = IndexErrorHandling::IEH_ABORT;
+    pub const ClearTrack: Self = IndexErrorHandling::IEH_CLEAR_TRACK;
+    pub const StopTrack: Self = IndexErrorHandling::IEH_STOP_TRACK;
+    pub const Ignore: Self = IndexErrorHandling::IEH_IGNORE;
+}
+
 create_enum!(
     SampleFormat,
     FFMS_SampleFormat,
@@ -72,6 +99,23 @@ create_enum!(
     (FMT_U8, FMT_S16, FMT_S32, FMT_FLT, FMT_DBL)
 );
 
+from_i32!(
+    SampleFormat,
+    FFMS_SampleFormat,
+    (FMT_U8, FMT_S16, FMT_S32, FMT_FLT, FMT_DBL)
+);
+
+impl SampleFormat {
+    pub fn bytes_per_sample(self) -> usize {
+        match self {
+            SampleFormat::FMT_U8 => 1,
+            SampleFormat::FMT_S16 => 2,
+            SampleFormat::FMT_S32 | SampleFormat::FMT_FLT => 4,
+            SampleFormat::FMT_DBL => 8,
+        }
+    }
+}
+
 create_enum!(
     LogLevels,
     FFMS_LogLevels,
@@ -105,6 +149,72 @@ from_i32!(
     )
 );
 
+display!(LogLevels,
+         (
+           LOG_QUIET: "quiet",
+           LOG_PANIC: "panic",
+           LOG_FATAL: "fatal",
+           LOG_ERROR: "error",
+           LOG_WARNING: "warning",
+           LOG_INFO: "info",
+           LOG_VERBOSE: "verbose",
+           LOG_DEBUG: "debug",
+           LOG_TRACE: "trace",
+         )
+);
+
+from_str!(LogLevels, "log level",
+          (
+            LOG_QUIET: "quiet",
+            LOG_PANIC: "panic",
+            LOG_FATAL: "fatal",
+            LOG_ERROR: "error",
+            LOG_WARNING: "warning",
+            LOG_INFO: "info",
+            LOG_VERBOSE: "verbose",
+            LOG_DEBUG: "debug",
+            LOG_TRACE: "trace",
+          )
+);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseEnumError {
+    kind: &'static str,
+    value: String,
+    expected: &'static [&'static str],
+}
+
+impl ParseEnumError {
+    pub(crate) fn new(
+        kind: &'static str,
+        value: &str,
+        expected: &'static [&'static str],
+    ) -> Self {
+        ParseEnumError {
+            kind,
+            value: value.to_owned(),
+            expected,
+        }
+    }
+}
+
+impl fmt::Display for ParseEnumError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "Unknown {} `{}`, expected one of: {}",
+            self.kind,
+            self.value,
+            self.expected.join(", "),
+        )
+    }
+}
+
+impl std::error::Error for ParseEnumError {}
+
+/// Error reported by FFMS2. The error type tells which operation failed and
+/// the sub type why, so callers can match on `parts()` to retry some errors,
+/// such as seeking ones, while giving up on others.
 pub struct Error {
     error: FFMS_ErrorInfo,
     buffer: [u8; 1024],
@@ -130,17 +240,74 @@ impl Default for Error {
 
 impl fmt::Debug for Error {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (error_type, sub_type, cause) = self.parts();
         write!(
             f,
             "Error: {}\nSubError: {}\n Cause: {}",
-            Errors::from_i32(self.error.ErrorType),
-            Errors::from_i32(self.error.SubType),
-            str::from_utf8(&self.buffer).unwrap(),
+            error_type, sub_type, cause,
         )
     }
 }
 
+impl std::error::Error for Error {}
+
 impl Error {
+    pub(crate) fn new(
+        error_type: Errors,
+        sub_type: Errors,
+        cause: &str,
+    ) -> Self {
+        let mut error: Error = Default::default();
+        error.error.ErrorType = error_type.to_errors() as i32;
+        error.error.SubType = sub_type.to_errors() as i32;
+        let len = cause.len().min(error.buffer.len() - 1);
+        error.buffer[..len].copy_from_slice(&cause.as_bytes()[..len]);
+        error
+    }
+
+    pub fn error_type(&self) -> Errors {
+        Errors::from_i32(self.error.ErrorType)
+    }
+
+    pub fn sub_type(&self) -> Errors {
+        Errors::from_i32(self.error.SubType)
+    }
+
+    /// Whether an index file or buffer could not be parsed, because it is
+    /// truncated, corrupt or was written by another FFMS2 version. The index
+    /// can be safely deleted and created again.
+    pub fn is_corrupt_index(&self) -> bool {
+        matches!(
+            (self.error_type(), self.sub_type()),
+            (
+                Errors::ERROR_PARSER | Errors::ERROR_INDEX,
+                Errors::ERROR_FILE_READ
+            ) | (Errors::ERROR_INDEX, Errors::ERROR_VERSION)
+        )
+    }
+
+    /// Message written by FFMS2, without the trailing NUL padding.
+    pub fn cause(&self) -> &str {
+        let len = self
+            .buffer
+            .iter()
+            .position(|b| *b == 0)
+            .unwrap_or(self.buffer.len());
+        str::from_utf8(&self.buffer[..len]).unwrap_or_default()
+    }
+
+    /// The components the formatted message is made of: error type, sub
+    /// type and cause.
+    pub fn parts(&self) -> (Errors, Errors, &str) {
+        (self.error_type(), self.sub_type(), self.cause())
+    }
+
     pub(crate) fn as_mut_ptr(&mut self) -> *mut FFMS_ErrorInfo {
         &mut self.error
     }
@@ -161,6 +328,36 @@ impl Log {
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Version {
+    pub major: u8,
+    pub minor: u8,
+    pub micro: u8,
+    pub bump: u8,
+}
+
+impl Version {
+    pub fn from_packed(version: u32) -> Self {
+        let [major, minor, micro, bump] = version.to_be_bytes();
+        Version {
+            major,
+            minor,
+            micro,
+            bump,
+        }
+    }
+}
+
+impl fmt::Display for Version {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.micro, self.bump
+        )
+    }
+}
+
 pub struct FFMS2;
 
 impl FFMS2 {
@@ -173,6 +370,17 @@ impl FFMS2 {
     pub fn Version() -> usize {
         unsafe { FFMS_GetVersion() as usize }
     }
+
+    pub fn version_parts() -> Version {
+        Version::from_packed(unsafe { FFMS_GetVersion() } as u32)
+    }
+
+    /// Whether FFmpeg has a decoder with the given name, such as `"h264"`
+    /// or `"libdav1d"`. FFMS2 does not enumerate codecs, so the lookup goes
+    /// through the libavcodec FFMS2 is linked against.
+    pub fn has_decoder(name: &str) -> bool {
+        ffmpeg_the_third::decoder::find_by_name(name).is_some()
+    }
 }
 
 impl Drop for FFMS2 {
@@ -182,3 +390,233 @@ impl Drop for FFMS2 {
         }
     }
 }
+
+pub fn media_summary(source_file: &Path) -> Result<String, Error> {
+    let indexer = Indexer::new(source_file)?;
+    let codecs: Vec<String> = (0..indexer.NumTracksI())
+        .map(|track| indexer.CodecNameI(track))
+        .collect();
+    indexer.enable_track_type(TrackType::TYPE_AUDIO)?;
+    let index = indexer.do_indexing(IndexErrorHandling::IEH_ABORT)?;
+
+    let mut summary = Vec::new();
+    let mut duration: f64 = 0.0;
+
+    if let Ok(track) = index.first_track_of_type(TrackType::TYPE_VIDEO) {
+        let mut video = VideoSource::new(
+            source_file,
+            track,
+            &index,
+            1,
+            SeekMode::SEEK_NORMAL,
+        )?;
+        let properties = video.GetVideoProperties();
+        let resolution =
+            Frame::GetFrame(&mut video, 0)?.get_frame_resolution();
+        let fps = format!(
+            "{:.3}",
+            properties.FPSNumerator as f64 / properties.FPSDenominator as f64
+        );
+        summary.push(format!(
+            "{}x{} {} {}fps",
+            resolution.width,
+            resolution.height,
+            codecs[track],
+            fps.trim_end_matches('0').trim_end_matches('.'),
+        ));
+        duration = duration.max(properties.LastEndTime);
+    }
+
+    if let Ok(track) = index.first_track_of_type(TrackType::TYPE_AUDIO) {
+        let audio = AudioSource::new(
+            source_file,
+            track,
+            &index,
+            FFMS_AudioDelayModes::FFMS_DELAY_NO_SHIFT as isize,
+        )?;
+        let properties = audio.GetAudioProperties();
+        summary.push(format!(
+            "{}ch {} {}Hz",
+            properties.Channels, codecs[track], properties.SampleRate,
+        ));
+        duration = duration.max(properties.LastEndTime);
+    }
+
+    let seconds = duration.round() as u64;
+    summary.push(format!(
+        "{:02}:{:02}:{:02}",
+        seconds / 3600,
+        seconds / 60 % 60,
+        seconds % 60
+    ));
+
+    Ok(summary.join(", "))
+}
+
+// Audio frames rarely end exactly on a video frame boundary
+const AV_DURATION_TOLERANCE: f64 = 0.05;
+
+fn duration_mismatch(video: f64, audio: f64) -> Option<f64> {
+    let difference = video - audio;
+    (difference.abs() > AV_DURATION_TOLERANCE).then_some(difference)
+}
+
+/// Difference in seconds between the end of the first video track and the
+/// end of the first audio track, positive when the video is longer. `None`
+/// when the durations match within 50 ms or a track is missing.
+pub fn av_duration_mismatch(source_file: &Path) -> Result<Option<f64>, Error> {
+    let indexer = Indexer::new(source_file)?;
+    indexer.enable_track_type(TrackType::TYPE_AUDIO)?;
+    let index = indexer.do_indexing(IndexErrorHandling::IEH_ABORT)?;
+
+    let (Ok(video_track), Ok(audio_track)) = (
+        index.first_track_of_type(TrackType::TYPE_VIDEO),
+        index.first_track_of_type(TrackType::TYPE_AUDIO),
+    ) else {
+        return Ok(None);
+    };
+
+    let video = VideoSource::new(
+        source_file,
+        video_track,
+        &index,
+        1,
+        SeekMode::SEEK_NORMAL,
+    )?;
+    let audio = AudioSource::new(
+        source_file,
+        audio_track,
+        &index,
+        FFMS_AudioDelayModes::FFMS_DELAY_NO_SHIFT as isize,
+    )?;
+
+    Ok(duration_mismatch(
+        video.GetVideoProperties().LastEndTime,
+        audio.GetAudioProperties().LastEndTime,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn packed_version() {
+        let version = Version::from_packed(0x0228_0100);
+        assert_eq!(
+            version,
+            Version {
+                major: 2,
+                minor: 40,
+                micro: 1,
+                bump: 0
+            }
+        );
+        assert_eq!(version.to_string(), "2.40.1.0");
+    }
+
+    #[test]
+    fn has_decoder() {
+        assert!(FFMS2::has_decoder("h264"));
+        assert!(!FFMS2::has_decoder("not-a-codec"));
+    }
+
+    #[test]
+    fn log_levels_round_trip() {
+        assert_eq!(LogLevels::all().len(), 9);
+        for name in LogLevels::all() {
+            let level: LogLevels = name.parse().unwrap();
+            assert_eq!(level.to_string(), *name);
+            assert_eq!(level.name(), *name);
+            assert_eq!(level.to_string().parse(), Ok(level));
+        }
+        assert_eq!("Verbose".parse(), Ok(LogLevels::LOG_VERBOSE));
+
+        let error = "loud".parse::<LogLevels>().unwrap_err();
+        assert!(error.to_string().starts_with("Unknown log level `loud`"));
+    }
+
+    #[test]
+    fn index_error_handling_round_trip() {
+        assert_eq!(IndexErrorHandling::all().len(), 4);
+        for name in IndexErrorHandling::all() {
+            let handling: IndexErrorHandling = name.parse().unwrap();
+            assert_eq!(handling.name(), *name);
+            assert_eq!(handling.name().parse(), Ok(handling));
+        }
+        assert_eq!(
+            IndexErrorHandling::Abort.to_string(),
+            "Index error aborting."
+        );
+        assert_eq!("abort".parse(), Ok(IndexErrorHandling::Abort));
+        assert_eq!("clear_track".parse(), Ok(IndexErrorHandling::ClearTrack));
+        assert_eq!("stop_track".parse(), Ok(IndexErrorHandling::StopTrack));
+        assert_eq!("ignore".parse(), Ok(IndexErrorHandling::Ignore));
+        assert!("retry".parse::<IndexErrorHandling>().is_err());
+    }
+
+    #[test]
+    fn duration_mismatch() {
+        assert_eq!(super::duration_mismatch(10.0, 10.0), None);
+        assert_eq!(super::duration_mismatch(10.0, 10.02), None);
+        assert_eq!(super::duration_mismatch(12.0, 10.0), Some(2.0));
+        assert_eq!(super::duration_mismatch(10.0, 10.5), Some(-0.5));
+    }
+
+    #[test]
+    fn corrupt_index_error() {
+        let truncated = Error::new(
+            Errors::ERROR_PARSER,
+            Errors::ERROR_FILE_READ,
+            "Failed to read from file",
+        );
+        assert!(truncated.is_corrupt_index());
+
+        let missing =
+            Error::new(Errors::ERROR_PARSER, Errors::ERROR_NO_FILE, "No file");
+        assert!(!missing.is_corrupt_index());
+    }
+
+    #[test]
+    fn match_error_kinds() {
+        let error = Error::new(
+            Errors::ERROR_FILE_READ,
+            Errors::ERROR_NO_FILE,
+            "/tmp/missing.mkv is not a file",
+        );
+        assert!(matches!(
+            error.parts(),
+            (Errors::ERROR_FILE_READ, Errors::ERROR_NO_FILE, _)
+        ));
+
+        let boxed: Box<dyn std::error::Error> = Box::new(error);
+        assert!(boxed
+            .to_string()
+            .ends_with("/tmp/missing.mkv is not a file"));
+    }
+
+    #[test]
+    fn error_parts() {
+        let error = Error::new(
+            Errors::ERROR_SEEKING,
+            Errors::ERROR_FILE_MISMATCH,
+            "Frame 3 is not a keyframe",
+        );
+        assert_eq!(
+            error.parts(),
+            (
+                Errors::ERROR_SEEKING,
+                Errors::ERROR_FILE_MISMATCH,
+                "Frame 3 is not a keyframe"
+            )
+        );
+        assert_eq!(
+            error.to_string(),
+            format!(
+                "Error: {}\nSubError: {}\n Cause: Frame 3 is not a keyframe",
+                Errors::ERROR_SEEKING,
+                Errors::ERROR_FILE_MISMATCH,
+            )
+        );
+    }
+}
@@ -0,0 +1,372 @@
+use crate::*;
+
+use ffms2_sys::*;
+
+use std::collections::HashMap;
+use std::ffi::CString;
+use std::sync::OnceLock;
+
+named_enum!(
+    PixelFormat,
+    (
+        NONE: "none",
+        YUV420P: "yuv420p",
+        YUYV422: "yuyv422",
+        UYVY422: "uyvy422",
+        RGB24: "rgb24",
+        BGR24: "bgr24",
+        YUV422P: "yuv422p",
+        YUV444P: "yuv444p",
+        YUV410P: "yuv410p",
+        YUV411P: "yuv411p",
+        YUV440P: "yuv440p",
+        YUVJ420P: "yuvj420p",
+        YUVJ422P: "yuvj422p",
+        YUVJ444P: "yuvj444p",
+        YUVA420P: "yuva420p",
+        YUVA444P: "yuva444p",
+        GRAY8: "gray",
+        GRAY10LE: "gray10le",
+        GRAY12LE: "gray12le",
+        GRAY16LE: "gray16le",
+        MONOWHITE: "monow",
+        MONOBLACK: "monob",
+        PAL8: "pal8",
+        NV12: "nv12",
+        NV21: "nv21",
+        NV16: "nv16",
+        ARGB: "argb",
+        RGBA: "rgba",
+        ABGR: "abgr",
+        BGRA: "bgra",
+        RGB48LE: "rgb48le",
+        RGBA64LE: "rgba64le",
+        X2RGB10LE: "x2rgb10le",
+        X2BGR10LE: "x2bgr10le",
+        GBRP: "gbrp",
+        GBRP10LE: "gbrp10le",
+        GBRP12LE: "gbrp12le",
+        GBRP16LE: "gbrp16le",
+        YUV420P10LE: "yuv420p10le",
+        YUV422P10LE: "yuv422p10le",
+        YUV444P10LE: "yuv444p10le",
+        YUV420P12LE: "yuv420p12le",
+        YUV422P12LE: "yuv422p12le",
+        YUV444P12LE: "yuv444p12le",
+        YUV420P16LE: "yuv420p16le",
+        YUV422P16LE: "yuv422p16le",
+        YUV444P16LE: "yuv444p16le",
+        P010LE: "p010le",
+        P012LE: "p012le",
+        P016LE: "p016le",
+        VAAPI: "vaapi",
+        DXVA2_VLD: "dxva2_vld",
+        D3D11: "d3d11",
+        CUDA: "cuda",
+        QSV: "qsv",
+        VIDEOTOOLBOX: "videotoolbox_vld",
+        DRM_PRIME: "drm_prime",
+        VULKAN: "vulkan",
+    )
+);
+
+// FFmpeg ids of `PixelFormat::ALL`, -1 for the names the linked FFmpeg
+// does not know
+fn pix_fmt_ids() -> &'static [i32] {
+    static IDS: OnceLock<Vec<i32>> = OnceLock::new();
+    IDS.get_or_init(|| {
+        PixelFormat::ALL
+            .iter()
+            .map(|format| {
+                let name = CString::new(format.as_str()).unwrap();
+                unsafe { FFMS_GetPixFmt(name.as_ptr()) }
+            })
+            .collect()
+    })
+}
+
+fn pix_fmt_map() -> &'static HashMap<i32, PixelFormat> {
+    static MAP: OnceLock<HashMap<i32, PixelFormat>> = OnceLock::new();
+    MAP.get_or_init(|| {
+        let mut map = HashMap::new();
+        for (format, id) in PixelFormat::ALL.iter().zip(pix_fmt_ids()) {
+            if *id != -1 {
+                map.entry(*id).or_insert(*format);
+            }
+        }
+        map
+    })
+}
+
+impl PixelFormat {
+    /// Format of an FFmpeg id, `NONE` for -1 and for formats this crate does
+    /// not describe.
+    pub fn new(pix_fmt: i32) -> Self {
+        pix_fmt_map()
+            .get(&pix_fmt)
+            .copied()
+            .unwrap_or(PixelFormat::NONE)
+    }
+
+    /// Looks a format up by its FFmpeg name, such as `"yuv420p"`.
+    pub fn from_name(name: &str) -> Option<Self> {
+        PixelFormat::ALL.iter().copied().find(|format| {
+            *format != PixelFormat::NONE && format.as_str() == name
+        })
+    }
+
+    /// FFmpeg name of the format, `None` for `NONE`.
+    pub fn name(self) -> Option<&'static str> {
+        (self != PixelFormat::NONE).then_some(self.as_str())
+    }
+
+    /// FFmpeg id of the format, -1 when the linked FFmpeg does not know it.
+    pub fn to_pix_fmt(self) -> i32 {
+        // The variants are declared in the order of `ALL`
+        pix_fmt_ids()[self as usize]
+    }
+
+    pub const fn chroma_subsampling(self) -> Option<(u8, u8)> {
+        use PixelFormat::*;
+        match self {
+            YUV420P | YUVJ420P | YUVA420P | YUV420P10LE | YUV420P12LE
+            | YUV420P16LE | NV12 | NV21 | P010LE | P012LE | P016LE => {
+                Some((1, 1))
+            }
+            YUV422P | YUVJ422P | YUV422P10LE | YUV422P12LE | YUV422P16LE
+            | YUYV422 | UYVY422 | NV16 => Some((1, 0)),
+            YUV444P | YUVJ444P | YUVA444P | YUV444P10LE | YUV444P12LE
+            | YUV444P16LE => Some((0, 0)),
+            YUV410P => Some((2, 2)),
+            YUV411P => Some((2, 0)),
+            YUV440P => Some((0, 1)),
+            _ => None,
+        }
+    }
+
+    /// Horizontal chroma subsampling as a shift of the luma width, 0 for
+    /// formats without subsampled planes.
+    pub const fn log2_chroma_w(self) -> u8 {
+        match self.chroma_subsampling() {
+            Some((w, _)) => w,
+            None => 0,
+        }
+    }
+
+    /// Vertical chroma subsampling as a shift of the luma height, 0 for
+    /// formats without subsampled planes.
+    pub const fn log2_chroma_h(self) -> u8 {
+        match self.chroma_subsampling() {
+            Some((_, h)) => h,
+            None => 0,
+        }
+    }
+
+    /// Number of planes the data is stored in, the palette of `PAL8`
+    /// included. 0 for hardware formats, whose data is not in memory.
+    pub const fn planes_count(self) -> usize {
+        use PixelFormat::*;
+        match self {
+            NONE | VAAPI | DXVA2_VLD | D3D11 | CUDA | QSV | VIDEOTOOLBOX
+            | DRM_PRIME | VULKAN => 0,
+            YUYV422 | UYVY422 | RGB24 | BGR24 | GRAY8 | GRAY10LE
+            | GRAY12LE | GRAY16LE | MONOWHITE | MONOBLACK | ARGB | RGBA
+            | ABGR | BGRA | RGB48LE | RGBA64LE | X2RGB10LE | X2BGR10LE => 1,
+            PAL8 | NV12 | NV21 | NV16 | P010LE | P012LE | P016LE => 2,
+            YUVA420P | YUVA444P => 4,
+            _ => 3,
+        }
+    }
+
+    /// Number of color components, counting alpha and palette indices as
+    /// one component each. 0 for hardware formats.
+    pub const fn components(self) -> u8 {
+        use PixelFormat::*;
+        match self {
+            NONE | VAAPI | DXVA2_VLD | D3D11 | CUDA | QSV | VIDEOTOOLBOX
+            | DRM_PRIME | VULKAN => 0,
+            GRAY8 | GRAY10LE | GRAY12LE | GRAY16LE | MONOWHITE | MONOBLACK
+            | PAL8 => 1,
+            YUVA420P | YUVA444P | ARGB | RGBA | ABGR | BGRA | RGBA64LE => 4,
+            _ => 3,
+        }
+    }
+
+    /// Average number of bits stored per pixel, padding bits included.
+    /// 0 for hardware formats.
+    pub const fn bits_per_pixel(self) -> u32 {
+        use PixelFormat::*;
+        match self {
+            NONE | VAAPI | DXVA2_VLD | D3D11 | CUDA | QSV | VIDEOTOOLBOX
+            | DRM_PRIME | VULKAN => 0,
+            MONOWHITE | MONOBLACK => 1,
+            YUV410P => 9,
+            GRAY8 | PAL8 => 8,
+            YUV420P | YUVJ420P | YUV411P | NV12 | NV21 => 12,
+            YUYV422 | UYVY422 | YUV422P | YUVJ422P | YUV440P | NV16
+            | GRAY10LE | GRAY12LE | GRAY16LE => 16,
+            YUVA420P => 20,
+            RGB24 | BGR24 | YUV444P | YUVJ444P | GBRP | YUV420P10LE
+            | YUV420P12LE | YUV420P16LE | P010LE | P012LE | P016LE => 24,
+            YUVA444P | ARGB | RGBA | ABGR | BGRA | X2RGB10LE | X2BGR10LE
+            | YUV422P10LE | YUV422P12LE | YUV422P16LE => 32,
+            RGB48LE | GBRP10LE | GBRP12LE | GBRP16LE | YUV444P10LE
+            | YUV444P12LE | YUV444P16LE => 48,
+            RGBA64LE => 64,
+        }
+    }
+}
+
+impl PixelFormat {
+    pub fn frame_size(self, width: usize, height: usize) -> Option<usize> {
+        use PixelFormat::*;
+        let pixels = width.checked_mul(height)?;
+        match self {
+            NONE | VAAPI | DXVA2_VLD | D3D11 | CUDA | QSV | VIDEOTOOLBOX
+            | DRM_PRIME | VULKAN => None,
+            MONOWHITE | MONOBLACK => width.div_ceil(8).checked_mul(height),
+            PAL8 => pixels.checked_add(1024),
+            GRAY8 => Some(pixels),
+            GRAY10LE | GRAY12LE | GRAY16LE => pixels.checked_mul(2),
+            YUYV422 | UYVY422 => {
+                width.div_ceil(2).checked_mul(4)?.checked_mul(height)
+            }
+            RGB24 | BGR24 | GBRP => pixels.checked_mul(3),
+            ARGB | RGBA | ABGR | BGRA | X2RGB10LE | X2BGR10LE => {
+                pixels.checked_mul(4)
+            }
+            RGB48LE | GBRP10LE | GBRP12LE | GBRP16LE => pixels.checked_mul(6),
+            RGBA64LE => pixels.checked_mul(8),
+            _ => {
+                let (log2_chroma_w, log2_chroma_h) =
+                    self.chroma_subsampling()?;
+                let chroma = width
+                    .div_ceil(1 << log2_chroma_w)
+                    .checked_mul(height.div_ceil(1 << log2_chroma_h))?;
+                let planes = match self {
+                    YUVA420P | YUVA444P => pixels
+                        .checked_mul(2)?
+                        .checked_add(chroma.checked_mul(2)?)?,
+                    _ => pixels.checked_add(chroma.checked_mul(2)?)?,
+                };
+                let bytes = match self {
+                    YUV420P10LE | YUV422P10LE | YUV444P10LE | YUV420P12LE
+                    | YUV422P12LE | YUV444P12LE | YUV420P16LE
+                    | YUV422P16LE | YUV444P16LE | P010LE | P012LE | P016LE => {
+                        2
+                    }
+                    _ => 1,
+                };
+                planes.checked_mul(bytes)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn chroma_subsampling() {
+        assert_eq!(PixelFormat::YUV420P.chroma_subsampling(), Some((1, 1)));
+        assert_eq!(PixelFormat::YUV422P.chroma_subsampling(), Some((1, 0)));
+        assert_eq!(PixelFormat::YUV444P.chroma_subsampling(), Some((0, 0)));
+        assert_eq!(PixelFormat::RGB24.chroma_subsampling(), None);
+    }
+
+    #[test]
+    fn names() {
+        let yuv420p = PixelFormat::from_name("yuv420p").unwrap();
+        assert_eq!(yuv420p, PixelFormat::YUV420P);
+        assert_eq!(yuv420p.name(), Some("yuv420p"));
+        assert_eq!(PixelFormat::GRAY8.name(), Some("gray"));
+        assert_eq!(PixelFormat::NONE.name(), None);
+        assert_eq!(PixelFormat::from_name("none"), None);
+        assert_eq!(PixelFormat::from_name("yuv999p"), None);
+        for format in PixelFormat::ALL.iter().skip(1) {
+            assert_eq!(
+                PixelFormat::from_name(format.name().unwrap()),
+                Some(*format)
+            );
+        }
+    }
+
+    #[test]
+    fn pix_fmt_lookups() {
+        assert_eq!(PixelFormat::new(-1), PixelFormat::NONE);
+        assert_eq!(PixelFormat::NONE.to_pix_fmt(), -1);
+        assert_eq!(PixelFormat::new(i32::MAX), PixelFormat::NONE);
+        for format in PixelFormat::ALL.iter().skip(1) {
+            let id = format.to_pix_fmt();
+            if id != -1 {
+                assert_eq!(PixelFormat::new(id), *format);
+            }
+        }
+        assert_ne!(PixelFormat::YUV420P.to_pix_fmt(), -1);
+    }
+
+    #[test]
+    fn log2_chroma() {
+        let log2_chroma = |format: PixelFormat| {
+            (format.log2_chroma_w(), format.log2_chroma_h())
+        };
+        assert_eq!(log2_chroma(PixelFormat::YUV420P), (1, 1));
+        assert_eq!(log2_chroma(PixelFormat::YUV422P), (1, 0));
+        assert_eq!(log2_chroma(PixelFormat::YUV444P), (0, 0));
+        assert_eq!(log2_chroma(PixelFormat::RGB24), (0, 0));
+    }
+
+    #[test]
+    fn planes_count() {
+        assert_eq!(PixelFormat::YUV420P.planes_count(), 3);
+        assert_eq!(PixelFormat::YUVA444P.planes_count(), 4);
+        assert_eq!(PixelFormat::NV12.planes_count(), 2);
+        assert_eq!(PixelFormat::PAL8.planes_count(), 2);
+        assert_eq!(PixelFormat::GBRP16LE.planes_count(), 3);
+        assert_eq!(PixelFormat::RGB24.planes_count(), 1);
+        assert_eq!(PixelFormat::NONE.planes_count(), 0);
+        assert_eq!(PixelFormat::CUDA.planes_count(), 0);
+    }
+
+    #[test]
+    fn components_and_bits() {
+        assert_eq!(PixelFormat::YUV420P.components(), 3);
+        assert_eq!(PixelFormat::YUVA444P.components(), 4);
+        assert_eq!(PixelFormat::GRAY8.components(), 1);
+        assert_eq!(PixelFormat::YUV420P.bits_per_pixel(), 12);
+        assert_eq!(PixelFormat::RGB24.bits_per_pixel(), 24);
+        assert_eq!(PixelFormat::YUV422P10LE.bits_per_pixel(), 32);
+        assert_eq!(PixelFormat::CUDA.bits_per_pixel(), 0);
+    }
+
+    #[test]
+    fn frame_size() {
+        assert_eq!(PixelFormat::YUV420P.frame_size(4, 4), Some(24));
+        assert_eq!(PixelFormat::YUV420P.frame_size(5, 5), Some(43));
+        assert_eq!(PixelFormat::YUV422P10LE.frame_size(4, 2), Some(32));
+        assert_eq!(PixelFormat::NV12.frame_size(4, 4), Some(24));
+        assert_eq!(PixelFormat::RGB24.frame_size(640, 360), Some(691200));
+        assert_eq!(PixelFormat::MONOBLACK.frame_size(10, 2), Some(4));
+        assert_eq!(PixelFormat::CUDA.frame_size(640, 360), None);
+        assert_eq!(PixelFormat::RGB24.frame_size(usize::MAX, 2), None);
+    }
+
+    #[test]
+    fn frame_size_overflow() {
+        use PixelFormat::*;
+
+        // The pixel count fits, the bytes of the frame do not
+        let width = usize::MAX / 2 + 1;
+        for format in [GRAY16LE, RGB24, RGBA, RGB48LE, RGBA64LE] {
+            assert_eq!(format.frame_size(width, 1), None, "{:?}", format);
+        }
+        assert_eq!(YUV420P.frame_size(usize::MAX / 2, 2), None);
+        assert_eq!(YUV444P16LE.frame_size(usize::MAX / 3, 1), None);
+        assert_eq!(YUVA444P.frame_size(usize::MAX / 3, 1), None);
+        assert_eq!(YUYV422.frame_size(usize::MAX, 1), None);
+        assert_eq!(MONOBLACK.frame_size(usize::MAX, 9), None);
+        assert_eq!(PAL8.frame_size(usize::MAX, 1), None);
+        assert_eq!(GRAY8.frame_size(usize::MAX, 1), Some(usize::MAX));
+    }
+}
@@ -1,7 +1,13 @@
 use crate::*;
 
-simple_enum!(
+use ffms2_sys::*;
+
+use std::ops::RangeInclusive;
+
+create_enum!(
     ResampleFilterType,
+    FFMS_ResampleFilterType,
+    resample_filter_type,
     (
         RESAMPLE_FILTER_CUBIC,
         RESAMPLE_FILTER_SINC,
@@ -9,8 +15,10 @@ simple_enum!(
     )
 );
 
-simple_enum!(
+create_enum!(
     AudioDitherMethod,
+    FFMS_AudioDitherMethod,
+    audio_dither_method,
     (
         RESAMPLE_DITHER_NONE,
         RESAMPLE_DITHER_RECTANGULAR,
@@ -98,6 +106,146 @@ impl ResampleOptions {
     }
 }
 
+/// Builds `ResampleOptions` checking the ranges FFMS2 accepts, instead of
+/// letting libswresample reject or clamp them later.
+///
+/// ```no_run
+/// # use ffms2::audio::*;
+/// # use ffms2::resample::*;
+/// # use ffms2::*;
+/// # fn f(audio: &AudioSource) -> Result<(), ffms2::Error> {
+/// let options = ResampleOptionsBuilder::new(&audio.CreateResampleOptions())
+///     .sample_rate(48000)
+///     .sample_format(SampleFormat::FMT_FLT)
+///     .center_mix_level(0.707)
+///     .build()?;
+/// audio.SetOutputFormatA(&options)?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct ResampleOptionsBuilder {
+    resample: FFMS_ResampleOptions,
+}
+
+impl Default for ResampleOptionsBuilder {
+    fn default() -> Self {
+        ResampleOptionsBuilder {
+            resample: *ResampleOptions::default(),
+        }
+    }
+}
+
+impl ResampleOptionsBuilder {
+    pub const MIX_LEVEL_RANGE: RangeInclusive<f64> = -32.0..=32.0;
+    pub const KAISER_BETA_RANGE: RangeInclusive<usize> = 2..=16;
+
+    /// Starts from `options`, usually the ones returned by
+    /// `AudioSource::CreateResampleOptions`.
+    pub fn new(options: &ResampleOptions) -> Self {
+        ResampleOptionsBuilder {
+            resample: **options,
+        }
+    }
+
+    pub fn sample_rate(mut self, sample_rate: usize) -> Self {
+        self.resample.SampleRate = sample_rate as i32;
+        self
+    }
+
+    pub fn sample_format(mut self, sample_format: SampleFormat) -> Self {
+        self.resample.SampleFormat =
+            SampleFormat::to_sample_format(sample_format);
+        self
+    }
+
+    pub fn channel_layout(mut self, channel_layout: i64) -> Self {
+        self.resample.ChannelLayout = channel_layout;
+        self
+    }
+
+    pub fn center_mix_level(mut self, level: f64) -> Self {
+        self.resample.CenterMixLevel = level;
+        self
+    }
+
+    pub fn surround_mix_level(mut self, level: f64) -> Self {
+        self.resample.SurroundMixLevel = level;
+        self
+    }
+
+    pub fn lfe_mix_level(mut self, level: f64) -> Self {
+        self.resample.LFEMixLevel = level;
+        self
+    }
+
+    pub fn normalize(mut self, normalize: bool) -> Self {
+        self.resample.Normalize = normalize as i32;
+        self
+    }
+
+    pub fn filter_type(mut self, filter_type: ResampleFilterType) -> Self {
+        self.resample.FilterType =
+            ResampleFilterType::to_resample_filter_type(filter_type);
+        self
+    }
+
+    pub fn kaiser_beta(mut self, kaiser_beta: usize) -> Self {
+        self.resample.KaiserBeta = kaiser_beta as i32;
+        self
+    }
+
+    pub fn dither_method(mut self, dither_method: AudioDitherMethod) -> Self {
+        self.resample.DitherMethod =
+            AudioDitherMethod::to_audio_dither_method(dither_method);
+        self
+    }
+
+    pub fn build(self) -> Result<ResampleOptions, Error> {
+        let r = &self.resample;
+        let mix_levels = [
+            ("center", r.CenterMixLevel),
+            ("surround", r.SurroundMixLevel),
+            ("LFE", r.LFEMixLevel),
+        ];
+        for (name, level) in mix_levels {
+            if !Self::MIX_LEVEL_RANGE.contains(&level) {
+                return Err(invalid_option(&format!(
+                    "The {} mix level {} is outside of [-32, 32]",
+                    name, level
+                )));
+            }
+        }
+
+        let kaiser_beta = usize::try_from(r.KaiserBeta).unwrap_or(0);
+        if r.FilterType == FFMS_ResampleFilterType::FFMS_RESAMPLE_FILTER_KAISER
+            && !Self::KAISER_BETA_RANGE.contains(&kaiser_beta)
+        {
+            return Err(invalid_option(&format!(
+                "The Kaiser beta {} is outside of [2, 16]",
+                r.KaiserBeta
+            )));
+        }
+
+        if r.SampleRate <= 0 {
+            return Err(invalid_option(&format!(
+                "Invalid sample rate {}",
+                r.SampleRate
+            )));
+        }
+
+        Ok(ResampleOptions::create_struct(r))
+    }
+}
+
+fn invalid_option(cause: &str) -> Error {
+    Error::new(
+        Errors::ERROR_RESAMPLING,
+        Errors::ERROR_INVALID_ARGUMENT,
+        cause,
+    )
+}
+
 impl Drop for ResampleOptions {
     fn drop(&mut self) {
         let raw_resample = Box::into_raw(Box::new(self.resample));
@@ -106,3 +254,30 @@ impl Drop for ResampleOptions {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn builder() -> ResampleOptionsBuilder {
+        ResampleOptionsBuilder::default()
+            .sample_rate(48000)
+            .sample_format(SampleFormat::FMT_FLT)
+    }
+
+    #[test]
+    fn center_mix_level_out_of_range() {
+        let err = builder().center_mix_level(40.0).build().err().unwrap();
+        assert_eq!(err.sub_type(), Errors::ERROR_INVALID_ARGUMENT);
+        assert!(builder().center_mix_level(-32.0).build().is_ok());
+    }
+
+    #[test]
+    fn kaiser_beta_only_checked_for_kaiser() {
+        assert!(builder().kaiser_beta(0).build().is_ok());
+        let kaiser =
+            builder().filter_type(ResampleFilterType::RESAMPLE_FILTER_KAISER);
+        assert!(kaiser.kaiser_beta(1).build().is_err());
+        assert!(kaiser.kaiser_beta(9).build().is_ok());
+    }
+}
@@ -0,0 +1,84 @@
+use crate::frame::*;
+use crate::pixel::*;
+use crate::video::*;
+
+impl Frame {
+    /// Builds a frame over caller-owned planes, given as `(data, linesize)`
+    /// pairs.
+    ///
+    /// # Safety
+    ///
+    /// The frame keeps raw pointers to the planes without borrowing them,
+    /// so the planes must outlive the returned frame and every copy of it.
+    /// Each plane must also hold as many bytes as `Frame::planes` reads for
+    /// the given size and pixel format.
+    pub unsafe fn from_raw_planes(
+        width: usize,
+        height: usize,
+        pixel_format: PixelFormat,
+        planes: &[(&[u8], usize)],
+    ) -> Self {
+        let mut frame = *Frame::default();
+        for (i, (plane, linesize)) in planes.iter().take(4).enumerate() {
+            frame.Data[i] = plane.as_ptr();
+            frame.Linesize[i] = *linesize as i32;
+        }
+
+        let pix_fmt = pixel_format.to_pix_fmt();
+        frame.EncodedWidth = width as i32;
+        frame.EncodedHeight = height as i32;
+        frame.EncodedPixelFormat = pix_fmt;
+        frame.ScaledWidth = -1;
+        frame.ScaledHeight = -1;
+        frame.ConvertedPixelFormat = pix_fmt;
+        Frame::create_frame(&frame)
+    }
+}
+
+impl VideoProperties {
+    pub fn for_test(
+        num_frames: usize,
+        fps_numerator: usize,
+        fps_denominator: usize,
+    ) -> Self {
+        let mut properties = VideoProperties::default();
+        let frame_duration = fps_denominator as f64 / fps_numerator as f64;
+        properties.NumFrames = num_frames as i32;
+        properties.FPSNumerator = fps_numerator as i32;
+        properties.FPSDenominator = fps_denominator as i32;
+        properties.SARNum = 1;
+        properties.SARDen = 1;
+        properties.LastTime =
+            num_frames.saturating_sub(1) as f64 * frame_duration;
+        properties.LastEndTime = num_frames as f64 * frame_duration;
+        properties
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::slice;
+
+    #[test]
+    fn from_raw_planes() {
+        let luma = [16u8; 8 * 4];
+        let chroma = [128u8; 4 * 2];
+        let frame = unsafe {
+            Frame::from_raw_planes(
+                8,
+                4,
+                PixelFormat::YUV420P,
+                &[(&luma, 8), (&chroma, 4), (&chroma, 4)],
+            )
+        };
+
+        let resolution = frame.get_frame_resolution();
+        assert_eq!((resolution.width, resolution.height), (8, 4));
+        assert_eq!(frame.Linesize, [8, 4, 4, 0]);
+
+        let plane = unsafe { slice::from_raw_parts(frame.Data[1], 4 * 2) };
+        assert_eq!(plane, chroma);
+    }
+}
@@ -5,6 +5,9 @@ use crate::video::*;
 use crate::*;
 
 use std::ffi::CString;
+use std::io;
+use std::io::Write;
+use std::ops::Range;
 use std::path::Path;
 
 create_enum!(
@@ -34,6 +37,14 @@ from_i32!(
     )
 );
 
+impl TrackType {
+    /// Whether FFMS2 can index tracks of this type. Other tracks are always
+    /// skipped by the indexer.
+    pub fn is_indexable(self) -> bool {
+        matches!(self, TrackType::TYPE_VIDEO | TrackType::TYPE_AUDIO)
+    }
+}
+
 create_struct!(
     TrackTimeBase,
     track_time_base,
@@ -42,6 +53,124 @@ create_struct!(
     (0, 0)
 );
 
+serde_struct!(TrackTimeBase, track_time_base, (Num, Den));
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct GroupOfPictures {
+    pub keyframe: usize,
+    pub frames: Range<usize>,
+}
+
+fn groups_of_pictures(key_frames: &[bool]) -> Vec<GroupOfPictures> {
+    let mut starts: Vec<usize> = key_frames
+        .iter()
+        .enumerate()
+        .filter(|(n, key_frame)| **key_frame && *n != 0)
+        .map(|(n, _)| n)
+        .collect();
+    if !key_frames.is_empty() {
+        starts.insert(0, 0);
+    }
+
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, start)| GroupOfPictures {
+            keyframe: *start,
+            frames: *start
+                ..starts.get(i + 1).copied().unwrap_or(key_frames.len()),
+        })
+        .collect()
+}
+
+fn average_frame_duration(timestamps: &[f64]) -> Option<f64> {
+    match timestamps {
+        [first, .., last] => {
+            Some((last - first) / (timestamps.len() - 1) as f64 / 1000.0)
+        }
+        _ => None,
+    }
+}
+
+simple_enum!(FrameRateMode, (Constant, Variable, Unknown));
+
+// Millisecond timestamps round frame durations such as 41.708 ms either way
+const FRAME_DURATION_TOLERANCE: f64 = 1.0;
+
+fn frame_rate_mode(timestamps: &[f64]) -> FrameRateMode {
+    let durations = timestamps.windows(2).map(|pts| pts[1] - pts[0]);
+    let (min, max) = durations.fold((f64::MAX, f64::MIN), |(min, max), d| {
+        (min.min(d), max.max(d))
+    });
+
+    if timestamps.len() < 3 {
+        FrameRateMode::Unknown
+    } else if max - min <= FRAME_DURATION_TOLERANCE {
+        FrameRateMode::Constant
+    } else {
+        FrameRateMode::Variable
+    }
+}
+
+// Same output as FFMS_WriteTimecodes
+fn write_timecodes<W: Write>(
+    w: &mut W,
+    pts: impl Iterator<Item = i64>,
+    time_base: &TrackTimeBase,
+) -> io::Result<()> {
+    writeln!(w, "# timecode format v2")?;
+    for pts in pts {
+        writeln!(
+            w,
+            "{:.2}",
+            (pts * time_base.Num) as f64 / time_base.Den as f64
+        )?;
+    }
+    Ok(())
+}
+
+// Same output as the keyframe files of the ffmsindex tool
+fn write_keyframes<W: Write>(
+    w: &mut W,
+    key_frames: impl Iterator<Item = bool>,
+) -> io::Result<()> {
+    write!(w, "# keyframe format v1\nfps 0\n")?;
+    for (n, _) in key_frames.enumerate().filter(|(_, key_frame)| *key_frame) {
+        writeln!(w, "{}", n)?;
+    }
+    Ok(())
+}
+
+fn unsigned_timestamps(
+    pts: impl Iterator<Item = i64>,
+) -> impl Iterator<Item = Result<u64, Error>> {
+    pts.enumerate().map(|(n, pts)| {
+        u64::try_from(pts).map_err(|_| {
+            Error::new(
+                Errors::ERROR_TRACK,
+                Errors::ERROR_INVALID_ARGUMENT,
+                &format!("Frame {} has a negative timestamp {}", n, pts),
+            )
+        })
+    })
+}
+
+// FFMS2 reports no frames for tracks which were not indexed
+fn indexed_frames(num_frames: i32) -> Result<usize, Error> {
+    match usize::try_from(num_frames) {
+        Ok(frames) if frames > 0 => Ok(frames),
+        _ => Err(Error::new(
+            Errors::ERROR_TRACK,
+            Errors::ERROR_NOT_AVAILABLE,
+            "The track was not indexed",
+        )),
+    }
+}
+
+fn frames_or_zero(num_frames: i32) -> usize {
+    usize::try_from(num_frames).unwrap_or(0)
+}
+
 macro_rules! track_error {
     ($track:expr, $num_frames:ident) => {
         let $num_frames = unsafe { FFMS_GetNumFrames($track) };
@@ -51,6 +180,16 @@ macro_rules! track_error {
     };
 }
 
+/// A track of an indexed source.
+///
+/// FFMS2 only hands out decoded data and per-frame metadata: the compressed
+/// packets are never exposed by its API, so stream-copy and remuxing have
+/// to be done with a demuxer such as FFmpeg's libavformat.
+///
+/// For the same reason the coded size of frames is unknown: `FrameInfo`
+/// only carries timestamps and flags, so neither per-frame sizes nor the
+/// bitrate of a track can be computed. The size of the file divided by its
+/// duration is the closest estimate for single-track files.
 pub struct Track {
     track: *mut FFMS_Track,
 }
@@ -96,12 +235,86 @@ impl Track {
         }
     }
 
+    pub fn write_timecodes_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        write_timecodes(w, self.pts(), &self.TimeBase())
+    }
+
+    pub fn write_keyframes_to<W: Write>(&self, w: &mut W) -> io::Result<()> {
+        let key_frames =
+            (0..self.NumFrames()).map(|n| self.FrameInfo(n).KeyFrame() != 0);
+        write_keyframes(w, key_frames)
+    }
+
     pub fn FrameInfo(&self, Frame: usize) -> FrameInfo {
         let res_frame = unsafe { FFMS_GetFrameInfo(self.track, Frame as i32) };
         let ref_frame = unsafe { &*res_frame };
         FrameInfo::create_struct(ref_frame)
     }
 
+    /// Presentation timestamps of the frames in time base units, in frame
+    /// order. Negative timestamps, which some containers store for frames
+    /// preceding the start, are reported as errors.
+    pub fn timestamps(&self) -> impl Iterator<Item = Result<u64, Error>> + '_ {
+        unsigned_timestamps(self.pts())
+    }
+
+    fn pts(&self) -> impl Iterator<Item = i64> + '_ {
+        (0..self.NumFrames()).map(move |n| self.FrameInfo(n).PTS)
+    }
+
+    pub fn original_timestamps(&self) -> impl Iterator<Item = i64> + '_ {
+        (0..self.NumFrames()).map(move |n| self.FrameInfo(n).OriginalPTS)
+    }
+
+    /// Reordered frames show up as original timestamps which are not
+    /// monotonic in the order the frames are stored in the track. Fails for
+    /// tracks which were not indexed.
+    pub fn has_bframes(&self) -> Result<bool, Error> {
+        indexed_frames(unsafe { FFMS_GetNumFrames(self.track) })?;
+        let timestamps: Vec<i64> = self.original_timestamps().collect();
+        Ok(timestamps.windows(2).any(|pts| pts[1] < pts[0]))
+    }
+
+    /// Partitions the track at its key frames. Frames preceding the first
+    /// key frame, if any, form a group starting at frame 0. Fails for tracks
+    /// which were not indexed.
+    pub fn gops(&self) -> Result<Vec<GroupOfPictures>, Error> {
+        let frames = indexed_frames(unsafe { FFMS_GetNumFrames(self.track) })?;
+        let key_frames: Vec<bool> = (0..frames)
+            .map(|n| self.FrameInfo(n).KeyFrame() != 0)
+            .collect();
+        Ok(groups_of_pictures(&key_frames))
+    }
+
+    /// Detects variable frame rate from the differences between the
+    /// timestamps of consecutive frames. Tracks with fewer than three frames
+    /// are `Unknown`.
+    pub fn frame_rate_mode(&self) -> FrameRateMode {
+        frame_rate_mode(&self.timestamps_ms())
+    }
+
+    /// Mean difference in seconds between the timestamps of consecutive
+    /// frames, which unlike the declared frame rate also holds for variable
+    /// frame rate tracks. Fails for tracks with fewer than two frames.
+    pub fn average_frame_duration(&self) -> Result<f64, Error> {
+        average_frame_duration(&self.timestamps_ms()).ok_or_else(|| {
+            Error::new(
+                Errors::ERROR_TRACK,
+                Errors::ERROR_NOT_AVAILABLE,
+                "At least two frames are needed to compute a frame duration",
+            )
+        })
+    }
+
+    fn timestamps_ms(&self) -> Vec<f64> {
+        let time_base = self.TimeBase();
+        self.pts()
+            .map(|pts| {
+                pts as f64 * time_base.Num as f64 / time_base.Den as f64
+            })
+            .collect()
+    }
+
     pub fn TimeBase(&self) -> TrackTimeBase {
         let res_track = unsafe { FFMS_GetTimeBase(self.track) };
         let ref_track = unsafe { &*res_track };
@@ -119,4 +332,134 @@ impl Track {
         track_error!(self.track, num_frames);
         num_frames as usize
     }
+
+    /// Number of indexed frames, 0 for tracks which were not indexed
+    /// instead of panicking like `NumFrames`. Video tracks count decoded
+    /// frames while audio tracks count packets, each holding many samples.
+    pub fn frame_count(&self) -> usize {
+        frames_or_zero(unsafe { FFMS_GetNumFrames(self.track) })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn indexable_track_types() {
+        assert!(TrackType::TYPE_VIDEO.is_indexable());
+        assert!(TrackType::TYPE_AUDIO.is_indexable());
+        assert!(!TrackType::TYPE_SUBTITLE.is_indexable());
+        assert!(!TrackType::TYPE_ATTACHMENT.is_indexable());
+    }
+
+    #[test]
+    fn timecodes_file_format() {
+        let time_base = TrackTimeBase {
+            track_time_base: FFMS_TrackTimeBase { Num: 1, Den: 90 },
+        };
+        let mut timecodes = Vec::new();
+        write_timecodes(
+            &mut timecodes,
+            [0, 3003, 6006].into_iter(),
+            &time_base,
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(timecodes).unwrap(),
+            "# timecode format v2\n0.00\n33.37\n66.73\n"
+        );
+    }
+
+    #[test]
+    fn keyframes_file_format() {
+        let mut keyframes = Vec::new();
+        write_keyframes(
+            &mut keyframes,
+            [true, false, false, true].into_iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            String::from_utf8(keyframes).unwrap(),
+            "# keyframe format v1\nfps 0\n0\n3\n"
+        );
+    }
+
+    #[test]
+    fn timestamps_in_frame_order() {
+        let pts = [0, 1001, 2002, 3003];
+        let timestamps: Vec<u64> = unsigned_timestamps(pts.into_iter())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(timestamps, [0, 1001, 2002, 3003]);
+
+        let mut negative = unsigned_timestamps([-1001, 0].into_iter());
+        assert_eq!(
+            negative.next().unwrap().unwrap_err().sub_type(),
+            Errors::ERROR_INVALID_ARGUMENT
+        );
+        assert_eq!(negative.next().unwrap().unwrap(), 0);
+    }
+
+    #[test]
+    fn unindexed_track_error() {
+        assert_eq!(indexed_frames(240).unwrap(), 240);
+        for num_frames in [0, -1] {
+            assert_eq!(
+                indexed_frames(num_frames).unwrap_err().sub_type(),
+                Errors::ERROR_NOT_AVAILABLE
+            );
+        }
+    }
+
+    #[test]
+    fn unindexed_frame_count() {
+        assert_eq!(frames_or_zero(240), 240);
+        assert_eq!(frames_or_zero(0), 0);
+        assert_eq!(frames_or_zero(-1), 0);
+    }
+
+    #[test]
+    fn frame_rate_modes() {
+        let ntsc: Vec<f64> =
+            (0..10).map(|n| (n as f64 * 41.708).round()).collect();
+        assert_eq!(frame_rate_mode(&ntsc), FrameRateMode::Constant);
+
+        let vfr = [0.0, 40.0, 80.0, 113.0, 146.0, 179.0];
+        assert_eq!(frame_rate_mode(&vfr), FrameRateMode::Variable);
+
+        assert_eq!(frame_rate_mode(&[0.0, 40.0]), FrameRateMode::Unknown);
+    }
+
+    #[test]
+    fn average_frame_durations() {
+        let cfr: Vec<f64> = (0..30)
+            .map(|n| (n as f64 * 1000.0 / 30.0).round())
+            .collect();
+        let duration = average_frame_duration(&cfr).unwrap();
+        assert!((duration - 1.0 / 30.0).abs() < 1e-4);
+
+        let vfr = [0.0, 40.0, 80.0, 100.0, 120.0];
+        assert_eq!(average_frame_duration(&vfr), Some(0.03));
+
+        assert_eq!(average_frame_duration(&[0.0]), None);
+    }
+
+    #[test]
+    fn gops_cover_all_frames() {
+        let key_frames =
+            [false, true, false, false, true, true, false, false, false];
+        let gops = groups_of_pictures(&key_frames);
+
+        assert_eq!(
+            gops.iter()
+                .map(|gop| gop.frames.clone())
+                .collect::<Vec<_>>(),
+            [0..1, 1..4, 4..5, 5..9]
+        );
+        assert!(gops
+            .windows(2)
+            .all(|g| g[0].frames.end == g[1].frames.start));
+        assert!(groups_of_pictures(&[]).is_empty());
+    }
 }
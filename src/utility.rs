@@ -2,13 +2,44 @@ extern crate paste;
 
 macro_rules! simple_enum {
     ($enum:ident, ($($field_name:ident),*$(,)*)) => {
-        #[derive(Clone, Copy, Debug)]
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
         pub enum $enum {
             $($field_name,)*
         }
     }
 }
 
+// Enums of FFmpeg values FFMS2 passes through as plain integers, any value
+// not listed maps to the fallback variant.
+macro_rules! numbered_enum {
+    ($enum:ident, $fallback:ident: $fallback_value:literal,
+    ($($field_name:ident: $value:literal),*$(,)*)) => {
+
+        simple_enum!($enum, ($($field_name,)* $fallback));
+
+        impl $enum {
+            pub const fn new(value: i32) -> Self {
+                match value {
+                    $(
+                        $value => $enum::$field_name,
+                    )*
+                    _ => $enum::$fallback,
+                }
+            }
+
+            pub const fn value(self) -> i32 {
+                match self {
+                    $(
+                        $enum::$field_name => $value,
+                    )*
+                    $enum::$fallback => $fallback_value,
+                }
+            }
+        }
+    }
+}
+
 macro_rules! create_enum {
     ($enum:ident, $type:ident, $func_name:ident,
     ($($field_name:ident),*$(,)*)) => {
@@ -63,11 +94,65 @@ macro_rules! display {
     }
 }
 
+macro_rules! named_enum {
+    ($enum:ident, ($($field_name:ident: $name:expr),*$(,)*)) => {
+
+        simple_enum!($enum, ($($field_name,)*));
+
+        display!($enum, ($($field_name: $name,)*));
+
+        impl $enum {
+            pub(crate) const ALL: &'static [$enum] = &[$($enum::$field_name,)*];
+
+            pub(crate) const fn as_str(self) -> &'static str {
+                match self {
+                    $(
+                        $enum::$field_name => $name,
+                    )*
+                }
+            }
+        }
+    }
+}
+
+macro_rules! from_str {
+    ($enum:ident, $kind:expr, ($($field_name:ident: $name:expr),*$(,)*)) => {
+        impl $enum {
+            pub fn all() -> &'static [&'static str] {
+                &[$($name,)*]
+            }
+
+            /// Name accepted by `FromStr`, one of `all()`.
+            pub fn name(&self) -> &'static str {
+                match self {
+                    $(
+                        $enum::$field_name => $name,
+                    )*
+                }
+            }
+        }
+
+        impl std::str::FromStr for $enum {
+            type Err = ParseEnumError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                $(
+                    if s.eq_ignore_ascii_case($name) {
+                        return Ok($enum::$field_name);
+                    }
+                )*
+
+                Err(ParseEnumError::new($kind, s, $enum::all()))
+            }
+        }
+    }
+}
+
 macro_rules! errors {
     ($enum:ident, $type:ident,
     ($($field_name:ident: $field_err:expr),*$(,)*)) => {
 
-        simple_enum!($enum, ($($field_name,)*));
+        create_enum!($enum, $type, errors, ($($field_name,)*));
 
         display!($enum, ($($field_name: $field_err,)*));
 
@@ -109,7 +194,11 @@ macro_rules! implement_deref {
                 &self.$param
             }
         }
+    };
+}
 
+macro_rules! implement_deref_mut {
+    ($struct:ident, $param:ident) => {
         impl std::ops::DerefMut for $struct {
             fn deref_mut(&mut self) -> &mut Self::Target {
                 &mut self.$param
@@ -118,10 +207,96 @@ macro_rules! implement_deref {
     };
 }
 
+// Serializes the listed fields of the wrapped FFMS2 struct by name. Missing
+// fields keep their default value when deserializing.
+macro_rules! serde_struct {
+    ($struct:ident, $param:ident, ($($field_name:ident),*$(,)*)) => {
+        #[cfg(feature = "serde")]
+        impl serde::Serialize for $struct {
+            fn serialize<S: serde::Serializer>(
+                &self,
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                use serde::ser::SerializeStruct;
+
+                const FIELDS: &[&str] = &[$(stringify!($field_name),)*];
+                let mut state =
+                    serializer.serialize_struct(stringify!($struct), FIELDS.len())?;
+                $(
+                    state.serialize_field(
+                        stringify!($field_name),
+                        &self.$param.$field_name,
+                    )?;
+                )*
+                state.end()
+            }
+        }
+
+        #[cfg(feature = "serde")]
+        impl<'de> serde::Deserialize<'de> for $struct {
+            fn deserialize<D: serde::Deserializer<'de>>(
+                deserializer: D,
+            ) -> Result<Self, D::Error> {
+                struct Visitor;
+
+                impl<'de> serde::de::Visitor<'de> for Visitor {
+                    type Value = $struct;
+
+                    fn expecting(
+                        &self,
+                        formatter: &mut std::fmt::Formatter,
+                    ) -> std::fmt::Result {
+                        formatter.write_str(concat!("struct ", stringify!($struct)))
+                    }
+
+                    fn visit_map<A: serde::de::MapAccess<'de>>(
+                        self,
+                        mut map: A,
+                    ) -> Result<Self::Value, A::Error> {
+                        let mut value = $struct::default();
+                        while let Some(key) = map.next_key::<String>()? {
+                            match key.as_str() {
+                                $(
+                                    stringify!($field_name) => {
+                                        value.$param.$field_name = map.next_value()?;
+                                    }
+                                )*
+                                _ => {
+                                    map.next_value::<serde::de::IgnoredAny>()?;
+                                }
+                            }
+                        }
+                        Ok(value)
+                    }
+                }
+
+                const FIELDS: &[&str] = &[$(stringify!($field_name),)*];
+                deserializer.deserialize_struct(stringify!($struct), FIELDS, Visitor)
+            }
+        }
+    }
+}
+
 macro_rules! create_struct {
     ($struct:ident, $param:ident, $type:tt,
      ($($field_name:ident),*$(,)*),
      ($($field_default_expr:expr),*$(,)*)
+     ) => {
+
+        create_struct!($struct, $param, $type,
+                       ($($field_name,)*),
+                       ($($field_default_expr,)*),
+                       read_only);
+
+        implement_deref_mut!($struct, $param);
+
+    };
+    // Without `DerefMut`, for structs whose raw pointers safe code must not
+    // be able to change
+    ($struct:ident, $param:ident, $type:tt,
+     ($($field_name:ident),*$(,)*),
+     ($($field_default_expr:expr),*$(,)*),
+     read_only
      ) => {
 
         set_struct!($struct, $param, $type);
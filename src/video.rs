@@ -1,11 +1,15 @@
 use crate::frame::*;
 use crate::index::*;
+use crate::pixel::*;
+use crate::track::*;
 use crate::*;
 
 use ffms2_sys::*;
 
-use std::ffi::CString;
-use std::path::Path;
+use std::cell::{Cell, RefCell};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 create_enum!(
     SeekMode,
@@ -43,6 +47,16 @@ create_enum!(
     (CR_UNSPECIFIED, CR_MPEG, CR_JPEG)
 );
 
+impl ColorRanges {
+    /// Unknown values are `CR_UNSPECIFIED`.
+    pub fn new(color_range: i32) -> Self {
+        [ColorRanges::CR_MPEG, ColorRanges::CR_JPEG]
+            .into_iter()
+            .find(|range| range.to_color_ranges() as i32 == color_range)
+            .unwrap_or(ColorRanges::CR_UNSPECIFIED)
+    }
+}
+
 create_struct!(
     VideoProperties,
     video_properties,
@@ -87,8 +101,497 @@ create_struct!(
     )
 );
 
+serde_struct!(
+    VideoProperties,
+    video_properties,
+    (
+        FPSDenominator,
+        FPSNumerator,
+        RFFDenominator,
+        RFFNumerator,
+        NumFrames,
+        SARNum,
+        SARDen,
+        CropTop,
+        CropBottom,
+        CropLeft,
+        CropRight,
+        TopFieldFirst,
+        ColorSpace,
+        ColorRange,
+        FirstTime,
+        LastTime,
+        Rotation,
+        Stereo3DType,
+        Stereo3DFlags,
+        LastEndTime,
+        HasMasteringDisplayPrimaries,
+        MasteringDisplayPrimariesX,
+        MasteringDisplayPrimariesY,
+        MasteringDisplayWhitePointX,
+        MasteringDisplayWhitePointY,
+        HasMasteringDisplayLuminance,
+        MasteringDisplayMinLuminance,
+        MasteringDisplayMaxLuminance,
+        HasContentLightLevel,
+        ContentLightLevelMax,
+        ContentLightLevelAverage,
+        Flip
+    )
+);
+
+/// Orientation of the video as a 2x2 matrix made of the flip and then the
+/// rotation FFMS2 reports. FFMS2 only exposes rotations in multiples of 90
+/// degrees, so the matrix only holds 0, 1 and -1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DisplayMatrix(pub [[i32; 2]; 2]);
+
+const VERTICAL_FLIP: [[i32; 2]; 2] = [[1, 0], [0, -1]];
+const HORIZONTAL_FLIP: [[i32; 2]; 2] = [[-1, 0], [0, 1]];
+
+impl DisplayMatrix {
+    /// `rotation` and `flip` as in `VideoProperties`, where a `flip` of 1
+    /// mirrors horizontally and -1 vertically, before the rotation.
+    pub fn new(rotation: i32, flip: i32) -> Self {
+        let (cos, sin) = match (rotation as f64 / 90.0).round() as i32 % 4 {
+            0 => (1, 0),
+            1 | -3 => (0, 1),
+            2 | -2 => (-1, 0),
+            _ => (0, -1),
+        };
+        let rotation = [[cos, -sin], [sin, cos]];
+        let flip = match flip.signum() {
+            0 => [[1, 0], [0, 1]],
+            1 => HORIZONTAL_FLIP,
+            _ => VERTICAL_FLIP,
+        };
+        DisplayMatrix(multiply(rotation, flip))
+    }
+
+    /// Rotation in degrees, from 0 to 270, and whether a vertical flip is
+    /// applied first. A horizontal flip is reported as a vertical flip
+    /// followed by a rotation of 180 degrees, which is the same transform.
+    pub fn to_degrees_and_flip(&self) -> (i32, bool) {
+        let [[a, b], [c, d]] = self.0;
+        let flipped = a * d - b * c < 0;
+        let rotation = if flipped {
+            multiply(self.0, VERTICAL_FLIP)
+        } else {
+            self.0
+        };
+        let degrees = match (rotation[0][0], rotation[1][0]) {
+            (0, 1) => 90,
+            (-1, 0) => 180,
+            (0, -1) => 270,
+            _ => 0,
+        };
+        (degrees, flipped)
+    }
+}
+
+fn multiply(a: [[i32; 2]; 2], b: [[i32; 2]; 2]) -> [[i32; 2]; 2] {
+    std::array::from_fn(|i| {
+        std::array::from_fn(|j| a[i][0] * b[0][j] + a[i][1] * b[1][j])
+    })
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct OutputProperties {
+    pub width: usize,
+    pub height: usize,
+    pub pixel_format: PixelFormat,
+}
+
+/// FFMS2 does not report the resolution in the video properties, the
+/// methods working on frame sizes take it from a decoded frame.
+impl VideoProperties {
+    pub fn display_matrix(&self) -> DisplayMatrix {
+        DisplayMatrix::new(
+            self.video_properties.Rotation,
+            self.video_properties.Flip,
+        )
+    }
+
+    pub fn is_image(&self) -> bool {
+        self.video_properties.NumFrames == 1
+    }
+
+    pub fn has_start_delay(&self) -> bool {
+        self.video_properties.FirstTime != 0.0
+    }
+
+    pub fn start_delay_seconds(&self) -> f64 {
+        self.video_properties.FirstTime
+    }
+
+    /// On-screen size of frames of `width` x `height` pixels once cropped,
+    /// scaled by the sample aspect ratio and then rotated.
+    pub fn corrected_display_size(
+        &self,
+        width: usize,
+        height: usize,
+    ) -> (usize, usize) {
+        let p = &self.video_properties;
+        let width = width
+            .saturating_sub(p.CropLeft.max(0) as usize)
+            .saturating_sub(p.CropRight.max(0) as usize);
+        let height = height
+            .saturating_sub(p.CropTop.max(0) as usize)
+            .saturating_sub(p.CropBottom.max(0) as usize);
+
+        let width = if p.SARNum > 0 && p.SARDen > 0 {
+            (width as f64 * p.SARNum as f64 / p.SARDen as f64).round() as usize
+        } else {
+            width
+        };
+
+        if p.Rotation.rem_euclid(180) == 90 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Reduced display aspect ratio of frames of `width` x `height` pixels,
+    /// such as `"16:9"`.
+    pub fn aspect_ratio_string(&self, width: usize, height: usize) -> String {
+        let p = &self.video_properties;
+        let (sar_num, sar_den) = if p.SARNum > 0 && p.SARDen > 0 {
+            (p.SARNum as u64, p.SARDen as u64)
+        } else {
+            (1, 1)
+        };
+
+        let num = width as u64 * sar_num;
+        let den = height as u64 * sar_den;
+        let divisor = gcd(num, den).max(1);
+        format!("{}:{}", num / divisor, den / divisor)
+    }
+
+    /// Whether frames of `size` from these properties and frames of
+    /// `other_size` from `other` can be concatenated without conversion.
+    pub fn is_compatible_with(
+        &self,
+        size: (usize, usize),
+        other: &VideoProperties,
+        other_size: (usize, usize),
+    ) -> bool {
+        let sar = |p: &FFMS_VideoProperties| match (p.SARNum, p.SARDen) {
+            (0, _) | (_, 0) => (1, 1),
+            (num, den) => (num as i64, den as i64),
+        };
+        let (a, b) = (&self.video_properties, &other.video_properties);
+        let (a_sar, b_sar) = (sar(a), sar(b));
+
+        size == other_size
+            && a.FPSNumerator as i64 * b.FPSDenominator as i64
+                == b.FPSNumerator as i64 * a.FPSDenominator as i64
+            && a_sar.0 * b_sar.1 == b_sar.0 * a_sar.1
+            && (a.CropTop, a.CropBottom, a.CropLeft, a.CropRight)
+                == (b.CropTop, b.CropBottom, b.CropLeft, b.CropRight)
+            && a.ColorSpace == b.ColorSpace
+            && a.ColorRange == b.ColorRange
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 {
+        a
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+// Checked before FFMS2 so that directories get a distinct error
+fn check_source_file(source_file: &Path) -> Result<(), Error> {
+    if source_file.is_file() {
+        Ok(())
+    } else {
+        Err(Error::new(
+            Errors::ERROR_FILE_READ,
+            Errors::ERROR_NO_FILE,
+            &format!("{} is not a file", source_file.display()),
+        ))
+    }
+}
+
+// The pixel format of a probe frame is one of the preferred ones unless
+// FFMS2 fell back to another format
+fn selected_format(
+    prefs: &[PixelFormat],
+    converted: i32,
+) -> Result<PixelFormat, Error> {
+    prefs
+        .iter()
+        .copied()
+        .find(|format| converted != -1 && format.to_pix_fmt() == converted)
+        .ok_or_else(|| {
+            Error::new(
+                Errors::ERROR_SCALING,
+                Errors::ERROR_UNSUPPORTED,
+                &format!(
+                    "None of {:?} was selected as output format, got {:?}",
+                    prefs,
+                    PixelFormat::new(converted)
+                ),
+            )
+        })
+}
+
+// Arguments of the last successful `SetOutputFormatV2` call
+#[derive(Clone, Debug, PartialEq)]
+struct OutputFormat {
+    target_formats: Vec<i32>,
+    width: usize,
+    height: usize,
+    resizer: Resizers,
+}
+
+fn replay_output_format<S, R>(
+    output_format: Option<OutputFormat>,
+    set: S,
+    reset: R,
+) -> Result<(), Error>
+where
+    S: FnOnce(OutputFormat) -> Result<(), Error>,
+    R: FnOnce(),
+{
+    match output_format {
+        Some(format) => set(format),
+        None => {
+            reset();
+            Ok(())
+        }
+    }
+}
+
+// Runs `f`, then `restore` whatever its outcome; an error of `f` wins over
+// one of `restore`
+fn with_restore<S, T, F, R>(
+    state: &mut S,
+    f: F,
+    restore: R,
+) -> Result<T, Error>
+where
+    F: FnOnce(&mut S) -> Result<T, Error>,
+    R: FnOnce(&mut S) -> Result<(), Error>,
+{
+    let result = f(state);
+    let restored = restore(state);
+    let value = result?;
+    restored?;
+    Ok(value)
+}
+
+// The output format is only known up front for a single target format
+fn output_for(
+    target_formats: &[i32],
+    width: usize,
+    height: usize,
+) -> Option<OutputProperties> {
+    match target_formats {
+        [pix_fmt] => Some(OutputProperties {
+            width,
+            height,
+            pixel_format: PixelFormat::new(*pix_fmt),
+        }),
+        _ => None,
+    }
+}
+
+// About 6 GB of RGB24 frames at 1080p
+const DEFAULT_DECODE_ALL_LIMIT: usize = 1000;
+
+fn video_properties<'a>(
+    video_prop: *const FFMS_VideoProperties,
+) -> &'a FFMS_VideoProperties {
+    assert!(!video_prop.is_null(), "FFMS2 returned no video properties");
+    unsafe { &*video_prop }
+}
+
+fn packed_rows(data: &[u8], linesize: usize, row_bytes: usize) -> Vec<u8> {
+    data.chunks(linesize)
+        .flat_map(|row| &row[..row_bytes])
+        .copied()
+        .collect()
+}
+
+fn collect_cancellable<T, F>(
+    count: usize,
+    cancel: &AtomicBool,
+    mut decode: F,
+) -> Result<Vec<T>, Error>
+where
+    F: FnMut(usize) -> Result<T, Error>,
+{
+    (0..count)
+        .map(|n| {
+            if cancel.load(Ordering::Relaxed) {
+                Err(Error::new(
+                    Errors::ERROR_DECODING,
+                    Errors::ERROR_CANCELLED,
+                    "Decoding cancelled",
+                ))
+            } else {
+                decode(n)
+            }
+        })
+        .collect()
+}
+
+// Reference frames kept by the decoder, the largest H.264 and HEVC allow
+const REFERENCE_FRAMES: usize = 16;
+
+fn decode_memory(
+    width: usize,
+    height: usize,
+    pixel_format: PixelFormat,
+    threads: usize,
+) -> usize {
+    // Hardware and unknown formats are estimated as 8-bit 4:2:0
+    let frame_size = pixel_format
+        .frame_size(width, height)
+        .or_else(|| PixelFormat::YUV420P.frame_size(width, height))
+        .unwrap_or(usize::MAX);
+    // Each decoding thread works on its own frame next to the references
+    frame_size.saturating_mul(REFERENCE_FRAMES + threads.max(1))
+}
+
+// References are as large as the coded frames whatever the output format,
+// the converted output frame only comes on top of them
+fn source_decode_memory(
+    coded: &Frame,
+    output: Option<OutputProperties>,
+    threads: usize,
+) -> usize {
+    let output_frame = output
+        .and_then(|output| {
+            output.pixel_format.frame_size(output.width, output.height)
+        })
+        .unwrap_or(0);
+    decode_memory(
+        coded.EncodedWidth.max(0) as usize,
+        coded.EncodedHeight.max(0) as usize,
+        PixelFormat::new(coded.EncodedPixelFormat),
+        threads,
+    )
+    .saturating_add(output_frame)
+}
+
+// Frames tried before giving up on a source with corrupt leading frames
+const MAX_LEADING_CORRUPT_FRAMES: usize = 64;
+
+fn first_decodable<T, F>(
+    frames_count: usize,
+    mut decode: F,
+) -> Result<(usize, T), Error>
+where
+    F: FnMut(usize) -> Result<T, Error>,
+{
+    let mut last_error = Error::new(
+        Errors::ERROR_DECODING,
+        Errors::ERROR_NOT_AVAILABLE,
+        "The track has no frames",
+    );
+    for n in 0..frames_count.min(MAX_LEADING_CORRUPT_FRAMES) {
+        match decode(n) {
+            Ok(frame) => return Ok((n, frame)),
+            Err(error) => last_error = error,
+        }
+    }
+    Err(last_error)
+}
+
+// Tracks as (type, indexed frames), returns the position and frames of the
+// first indexed video track
+fn first_video_track(
+    tracks: impl IntoIterator<Item = (TrackType, usize)>,
+) -> Result<(usize, usize), Error> {
+    tracks
+        .into_iter()
+        .enumerate()
+        .find(|(_, (track_type, frames))| {
+            *track_type == TrackType::TYPE_VIDEO && *frames > 0
+        })
+        .map(|(n, (_, frames))| (n, frames))
+        .ok_or_else(|| {
+            Error::new(
+                Errors::ERROR_INDEX,
+                Errors::ERROR_NOT_AVAILABLE,
+                "No indexed video track",
+            )
+        })
+}
+
+// Images have a single frame which can only be decoded linearly
+fn default_seek_mode(frames_count: usize) -> SeekMode {
+    if frames_count == 1 {
+        SeekMode::SEEK_LINEAR_NO_RW
+    } else {
+        SeekMode::SEEK_NORMAL
+    }
+}
+
+fn step_positions(
+    frames_count: usize,
+    step: usize,
+) -> impl Iterator<Item = usize> {
+    assert!(step >= 1, "The frame step must be at least 1");
+    (0..frames_count).step_by(step)
+}
+
+fn thumbnail_positions(
+    key_frames: &[usize],
+    frames_count: usize,
+    count: usize,
+) -> Vec<usize> {
+    let count = count.min(frames_count);
+    let mut positions: Vec<usize> = Vec::with_capacity(count);
+
+    for i in 0..count {
+        let target = (2 * i + 1) * frames_count / (2 * count);
+        let previous = positions.last().copied();
+        let key_frame = key_frames
+            [..key_frames.partition_point(|k| *k <= target)]
+            .last()
+            .copied()
+            .filter(|k| previous.is_none_or(|p| *k > p));
+        let position = key_frame
+            .unwrap_or_else(|| previous.map_or(target, |p| target.max(p + 1)));
+        positions.push(position);
+    }
+
+    positions
+}
+
+fn luma_histogram(luma: &[u8]) -> [u32; 256] {
+    let mut histogram = [0; 256];
+    for sample in luma {
+        histogram[*sample as usize] += 1;
+    }
+    histogram
+}
+
+// Half the sum of the absolute differences of the normalized bins
+fn histogram_difference(a: &[u32; 256], b: &[u32; 256]) -> f64 {
+    let a_total = a.iter().map(|n| *n as f64).sum::<f64>().max(1.0);
+    let b_total = b.iter().map(|n| *n as f64).sum::<f64>().max(1.0);
+    a.iter()
+        .zip(b)
+        .map(|(a, b)| (*a as f64 / a_total - *b as f64 / b_total).abs())
+        .sum::<f64>()
+        / 2.0
+}
+
 pub struct VideoSource {
     video_source: *mut FFMS_VideoSource,
+    source_file: PathBuf,
+    track: usize,
+    seek_mode: SeekMode,
+    threads: usize,
+    output: Cell<Option<OutputProperties>>,
+    output_format: RefCell<Option<OutputFormat>>,
+    decode_all_limit: usize,
 }
 
 unsafe impl Send for VideoSource {}
@@ -101,7 +604,9 @@ impl VideoSource {
         Threads: usize,
         SeekMode: SeekMode,
     ) -> Result<Self, Error> {
-        let source = CString::new(SourceFile.to_str().unwrap()).unwrap();
+        check_source_file(SourceFile)?;
+
+        let source = path_to_cstring(SourceFile, Errors::ERROR_FILE_READ)?;
         let mut error: Error = Default::default();
         let seek = SeekMode::to_seek_mode(SeekMode) as i32;
         let video_source = unsafe {
@@ -118,19 +623,83 @@ impl VideoSource {
         if video_source.is_null() {
             Err(error)
         } else {
-            Ok(VideoSource { video_source })
+            Ok(VideoSource {
+                video_source,
+                source_file: SourceFile.to_path_buf(),
+                track: Track,
+                seek_mode: SeekMode,
+                threads: Threads,
+                output: Cell::new(None),
+                output_format: RefCell::new(None),
+                decode_all_limit: DEFAULT_DECODE_ALL_LIMIT,
+            })
         }
     }
 
+    /// Opens the first indexed video track of `source_file`, whatever its
+    /// position among the other tracks, letting FFMS2 pick the number of
+    /// decoding threads. Single frame tracks such as images are opened with
+    /// `SEEK_LINEAR_NO_RW`, any other track with `SEEK_NORMAL`.
+    pub fn open_first_video(
+        index: &Index,
+        source_file: &Path,
+    ) -> Result<Self, Error> {
+        let (track, frames_count) =
+            first_video_track((0..index.tracks_count()).map(|n| {
+                let track = Track::TrackFromIndex(index, n);
+                (track.TrackType(), track.frame_count())
+            }))?;
+        VideoSource::new(
+            source_file,
+            track,
+            index,
+            0,
+            default_seek_mode(frames_count),
+        )
+    }
+
+    /// FFMS2 fixes the number of decoding threads when a source is created,
+    /// so the source is opened again with the new count. The output format
+    /// and decode limit carry over, the input format has to be set again.
+    pub fn set_thread_count(
+        &mut self,
+        index: &Index,
+        threads: usize,
+    ) -> Result<(), Error> {
+        let source_file = self.source_file.clone();
+        let mut reopened = VideoSource::new(
+            &source_file,
+            self.track,
+            index,
+            threads,
+            self.seek_mode,
+        )?;
+        reopened.decode_all_limit = self.decode_all_limit;
+        reopened.restore_output_format(
+            self.output.get(),
+            self.output_format.borrow().clone(),
+        )?;
+        *self = reopened;
+        Ok(())
+    }
+
+    /// FFMS2 always returns properties for an opened source, a null pointer
+    /// is treated as a broken invariant and panics instead of being
+    /// dereferenced.
     pub fn GetVideoProperties(&self) -> VideoProperties {
         let video_prop = unsafe { FFMS_GetVideoProperties(self.video_source) };
-        let ref_video = unsafe { &*video_prop };
 
         VideoProperties {
-            video_properties: *ref_video,
+            video_properties: *video_properties(video_prop),
         }
     }
 
+    /// Number of frames, without copying the whole `VideoProperties`.
+    pub fn frame_count(&self) -> usize {
+        let video_prop = unsafe { FFMS_GetVideoProperties(self.video_source) };
+        video_properties(video_prop).NumFrames as usize
+    }
+
     pub fn SetInputFormatV(
         &self,
         ColorSpace: usize,
@@ -187,6 +756,13 @@ impl VideoSource {
         if err != 0 {
             Err(error)
         } else {
+            self.output.set(output_for(TargetFormats, Width, Height));
+            *self.output_format.borrow_mut() = Some(OutputFormat {
+                target_formats: TargetFormats.clone(),
+                width: Width,
+                height: Height,
+                resizer: Resizer,
+            });
             Ok(())
         }
     }
@@ -195,6 +771,277 @@ impl VideoSource {
         unsafe {
             FFMS_ResetOutputFormatV(self.video_source);
         }
+        self.output.set(None);
+        *self.output_format.borrow_mut() = None;
+    }
+
+    /// Sets the output format to the first of `prefs` FFMS2 can convert to,
+    /// with the source resolution when `dims` is `None`, and decodes a probe
+    /// frame to report the pixel format that was actually selected. Fails
+    /// when FFMS2 fell back to a format outside of `prefs`.
+    pub fn negotiate_output(
+        &mut self,
+        prefs: &[PixelFormat],
+        dims: Option<(usize, usize)>,
+        resizer: Resizers,
+    ) -> Result<PixelFormat, Error> {
+        let (width, height) = match dims {
+            Some(dims) => dims,
+            None => {
+                let frame = Frame::GetFrame(self, 0)?;
+                (frame.EncodedWidth as usize, frame.EncodedHeight as usize)
+            }
+        };
+
+        let mut target_formats: Vec<i32> =
+            prefs.iter().map(|format| format.to_pix_fmt()).collect();
+        self.SetOutputFormatV2(&mut target_formats, width, height, resizer)?;
+
+        let probe = Frame::GetFrame(self, 0)?;
+        let pixel_format = selected_format(prefs, probe.ConvertedPixelFormat)?;
+        self.output.set(Some(OutputProperties {
+            width,
+            height,
+            pixel_format,
+        }));
+        Ok(pixel_format)
+    }
+
+    // Sets an output format requested earlier, possibly on another source
+    fn restore_output_format(
+        &self,
+        output: Option<OutputProperties>,
+        output_format: Option<OutputFormat>,
+    ) -> Result<(), Error> {
+        replay_output_format(
+            output_format,
+            |mut format| {
+                self.SetOutputFormatV2(
+                    &mut format.target_formats,
+                    format.width,
+                    format.height,
+                    format.resizer,
+                )
+            },
+            || self.ResetOutputFormatV(),
+        )?;
+        self.output.set(output);
+        Ok(())
+    }
+
+    /// Compares the target of an output format against the coded resolution
+    /// of the source, which is read from the first frame.
+    pub fn scaling_direction(
+        &mut self,
+        target_w: usize,
+        target_h: usize,
+    ) -> Result<ScalingDirection, Error> {
+        let frame = Frame::GetFrame(self, 0)?;
+        let source =
+            (frame.EncodedWidth as usize, frame.EncodedHeight as usize);
+        Ok(ScalingDirection::new(source, (target_w, target_h)))
+    }
+
+    /// Output resolution and pixel format set through a single target
+    /// format or `negotiate_output`, `None` when frames are decoded in the
+    /// source format or the selected format is not known yet.
+    pub fn output_properties(&self) -> Option<OutputProperties> {
+        self.output.get()
+    }
+
+    /// Rough estimate in bytes of the frame buffers FFmpeg allocates for
+    /// this source, from the coded size and format of the first frame, the
+    /// number of decoding threads and the worst case number of reference
+    /// frames, plus the converted output frame once an output format is set.
+    pub fn estimated_decode_memory(&mut self) -> Result<usize, Error> {
+        let first = Frame::GetFrame(self, 0)?;
+        Ok(source_decode_memory(
+            &first,
+            self.output.get(),
+            self.threads,
+        ))
+    }
+
+    /// Size in bytes of a frame decoded with the output format set through
+    /// a single target format or `negotiate_output`, `None` when the output
+    /// format is unknown.
+    pub fn output_frame_bytes(&self) -> Option<usize> {
+        let output = self.output.get()?;
+        output.pixel_format.frame_size(output.width, output.height)
+    }
+
+    pub fn total_output_bytes(&self) -> Option<usize> {
+        let frames_count = self.frame_count();
+        self.output_frame_bytes()?.checked_mul(frames_count)
+    }
+
+    /// Decodes `count` frames evenly spread across the track, moving each
+    /// position back to the closest key frame when that keeps the frames
+    /// distinct, since key frames are the cheapest to seek to.
+    pub fn thumbnails(
+        &mut self,
+        track: &Track,
+        count: usize,
+    ) -> Result<Vec<OwnedFrame>, Error> {
+        let key_frames: Vec<usize> = (0..track.NumFrames())
+            .filter(|n| track.FrameInfo(*n).KeyFrame() != 0)
+            .collect();
+
+        thumbnail_positions(&key_frames, track.NumFrames(), count)
+            .into_iter()
+            .map(|n| {
+                Frame::GetFrame(self, n).map(|frame| OwnedFrame::new(&frame))
+            })
+            .collect()
+    }
+
+    /// Indices of the frames of `track` whose luma histogram differs from
+    /// the one of the previous frame by more than `threshold`, from 0 for
+    /// identical histograms to 1 for disjoint ones. Every frame is decoded,
+    /// so the output format must have 8-bit luma, see `Frame::luma`.
+    pub fn scene_changes(
+        &mut self,
+        track: &Track,
+        threshold: f64,
+    ) -> Result<Vec<usize>, Error> {
+        let mut cuts = Vec::new();
+        let mut previous: Option<[u32; 256]> = None;
+
+        for n in 0..track.NumFrames() {
+            let frame = Frame::GetFrame(self, n)?;
+            let luma = frame.luma().ok_or_else(|| {
+                Error::new(
+                    Errors::ERROR_DECODING,
+                    Errors::ERROR_INVALID_ARGUMENT,
+                    "Scene detection needs an output format with 8-bit luma",
+                )
+            })?;
+            let histogram = luma_histogram(&luma);
+            if previous.is_some_and(|previous| {
+                histogram_difference(&previous, &histogram) > threshold
+            }) {
+                cuts.push(n);
+            }
+            previous = Some(histogram);
+        }
+
+        Ok(cuts)
+    }
+
+    pub fn frames(&mut self) -> FrameIterator<'_> {
+        let frames_count = self.frame_count();
+        FrameIterator {
+            cursor: FrameCursor::new(self, frames_count),
+        }
+    }
+
+    /// Decodes frame `n` along with its display time in seconds.
+    pub fn frame_with_timestamp(
+        &mut self,
+        track: &Track,
+        n: usize,
+    ) -> Result<(Frame, f64), Error> {
+        let frame = Frame::GetFrame(self, n)?;
+        let timestamp =
+            track.FrameInfo(n).timestamp_seconds(&track.TimeBase());
+        Ok((frame, timestamp))
+    }
+
+    /// Maximum number of frames `decode_all_rgb` accepts to decode, 1000 by
+    /// default.
+    pub fn set_decode_all_limit(&mut self, frames: usize) {
+        self.decode_all_limit = frames;
+    }
+
+    /// Decodes every frame of the track to packed RGB24 at the source
+    /// resolution, one buffer without row padding per frame. Tracks longer
+    /// than the limit set through `set_decode_all_limit` are rejected. The
+    /// output format set beforehand is restored afterwards.
+    pub fn decode_all_rgb(
+        &mut self,
+        track: &Track,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        self.decode_all_rgb_with_cancellation(track, &AtomicBool::new(false))
+    }
+
+    /// Same as `decode_all_rgb`, stopping with an `ERROR_CANCELLED` sub type
+    /// error before the next frame once `cancel` is set, for instance from
+    /// another thread through an `Arc<AtomicBool>`. The previous output
+    /// format is restored on cancellation as well.
+    pub fn decode_all_rgb_with_cancellation(
+        &mut self,
+        track: &Track,
+        cancel: &AtomicBool,
+    ) -> Result<Vec<Vec<u8>>, Error> {
+        let frames_count = track.NumFrames();
+        if frames_count > self.decode_all_limit {
+            return Err(Error::new(
+                Errors::ERROR_DECODING,
+                Errors::ERROR_INVALID_ARGUMENT,
+                &format!(
+                    "The track has {} frames, more than the limit of {}",
+                    frames_count, self.decode_all_limit
+                ),
+            ));
+        }
+
+        let output = self.output.get();
+        let output_format = self.output_format.borrow().clone();
+        with_restore(
+            self,
+            |video_source| {
+                video_source.negotiate_output(
+                    &[PixelFormat::RGB24],
+                    None,
+                    Resizers::RESIZER_BICUBIC,
+                )?;
+                collect_cancellable(frames_count, cancel, |n| {
+                    let frame = Frame::GetFrame(video_source, n)?;
+                    let width = frame.get_frame_resolution().width as usize;
+                    let data = frame.planes()[0].ok_or_else(|| {
+                        Error::new(
+                            Errors::ERROR_DECODING,
+                            Errors::ERROR_UNKNOWN,
+                            &format!("Frame {} has no data", n),
+                        )
+                    })?;
+                    Ok(packed_rows(
+                        data,
+                        frame.Linesize[0] as usize,
+                        3 * width,
+                    ))
+                })
+            },
+            |video_source| {
+                video_source.restore_output_format(output, output_format)
+            },
+        )
+    }
+
+    /// Decodes frames from the start of the track until one decodes without
+    /// errors, returning its number along with the frame. Only the first 64
+    /// frames are tried; on a fully corrupt track the error of the last
+    /// attempt is returned.
+    pub fn first_good_frame(
+        &mut self,
+        track: &Track,
+    ) -> Result<(usize, Frame), Error> {
+        first_decodable(track.NumFrames(), |n| Frame::GetFrame(self, n))
+    }
+
+    /// Decodes frames `0, step, 2 * step, ...` of the track.
+    ///
+    /// # Panics
+    ///
+    /// Panics if `step` is 0.
+    pub fn step_frames<'a>(
+        &'a mut self,
+        track: &Track,
+        step: usize,
+    ) -> impl Iterator<Item = Result<OwnedFrame, Error>> + 'a {
+        step_positions(track.NumFrames(), step).map(move |n| {
+            Frame::GetFrame(self, n).map(|frame| OwnedFrame::new(&frame))
+        })
     }
 
     pub(crate) fn as_mut_ptr(&mut self) -> *mut FFMS_VideoSource {
@@ -209,3 +1056,613 @@ impl Drop for VideoSource {
         }
     }
 }
+
+/// Decodes the frames of a `VideoSource` in order, copying each one out of
+/// the buffers of the source so it stays valid after the next is decoded.
+pub struct FrameIterator<'a> {
+    cursor: FrameCursor<&'a mut VideoSource>,
+}
+
+impl Iterator for FrameIterator<'_> {
+    type Item = Result<OwnedFrame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.cursor
+            .decode_next(|video_source, n| decode_owned(video_source, n))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.cursor.positions.size_hint()
+    }
+}
+
+fn decode_owned(
+    video_source: &mut VideoSource,
+    n: usize,
+) -> Result<OwnedFrame, Error> {
+    Frame::GetFrame(video_source, n).map(|frame| OwnedFrame::new(&frame))
+}
+
+// A source along with the frames still to be decoded from it, in order
+struct FrameCursor<S> {
+    source: S,
+    positions: Range<usize>,
+}
+
+impl<S> FrameCursor<S> {
+    fn new(source: S, frames_count: usize) -> Self {
+        FrameCursor {
+            source,
+            positions: 0..frames_count,
+        }
+    }
+
+    fn decode_next<T, F>(&mut self, decode: F) -> Option<T>
+    where
+        F: FnOnce(&mut S, usize) -> T,
+    {
+        let n = self.positions.next()?;
+        Some(decode(&mut self.source, n))
+    }
+}
+
+// Frames are decoded one ahead of the one handed out
+struct Prefetcher<S, T> {
+    cursor: FrameCursor<S>,
+    prefetched: Option<T>,
+}
+
+impl<S, T> Prefetcher<S, T> {
+    fn new<F>(source: S, frames_count: usize, decode: F) -> Self
+    where
+        F: FnOnce(&mut S, usize) -> T,
+    {
+        let mut cursor = FrameCursor::new(source, frames_count);
+        let prefetched = cursor.decode_next(decode);
+        Prefetcher { cursor, prefetched }
+    }
+
+    fn next<F>(&mut self, decode: F) -> Option<T>
+    where
+        F: FnOnce(&mut S, usize) -> T,
+    {
+        let frame = self.prefetched.take()?;
+        self.prefetched = self.cursor.decode_next(decode);
+        Some(frame)
+    }
+}
+
+pub struct PlaybackDecoder {
+    frames: Prefetcher<VideoSource, Result<OwnedFrame, Error>>,
+}
+
+impl PlaybackDecoder {
+    pub fn new(video_source: VideoSource) -> Self {
+        let frames_count = video_source.frame_count();
+        PlaybackDecoder {
+            frames: Prefetcher::new(video_source, frames_count, decode_owned),
+        }
+    }
+
+    pub fn into_inner(self) -> VideoSource {
+        self.frames.cursor.source
+    }
+}
+
+impl Iterator for PlaybackDecoder {
+    type Item = Result<OwnedFrame, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.frames.next(decode_owned)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hard_cut_histograms() {
+        let dark: Vec<u8> = (0..64).map(|i| 16 + i % 8).collect();
+        let bright: Vec<u8> = (0..64).map(|i| 200 + i % 8).collect();
+        let frames = [&dark, &dark, &dark, &bright, &bright];
+        let histograms: Vec<_> =
+            frames.iter().map(|luma| luma_histogram(luma)).collect();
+
+        let cuts: Vec<usize> = (1..histograms.len())
+            .filter(|n| {
+                histogram_difference(&histograms[n - 1], &histograms[*n]) > 0.5
+            })
+            .collect();
+        assert_eq!(cuts, [3]);
+        assert_eq!(histogram_difference(&histograms[2], &histograms[3]), 1.0);
+        assert_eq!(histogram_difference(&histograms[0], &histograms[1]), 0.0);
+    }
+
+    #[test]
+    fn thumbnail_positions_increase() {
+        let key_frames = [0, 12, 24, 36, 48];
+        assert_eq!(
+            thumbnail_positions(&key_frames, 60, 5),
+            [0, 12, 24, 36, 48]
+        );
+        assert_eq!(thumbnail_positions(&[0], 10, 5), [0, 3, 5, 7, 9]);
+        assert_eq!(thumbnail_positions(&[0], 3, 5), [0, 1, 2]);
+        assert!(thumbnail_positions(&[0], 10, 0).is_empty());
+    }
+
+    #[test]
+    fn decode_memory_grows_with_resolution() {
+        let sd = decode_memory(720, 480, PixelFormat::YUV420P, 4);
+        let uhd = decode_memory(3840, 2160, PixelFormat::YUV420P, 4);
+        assert!(uhd > sd);
+        assert!(decode_memory(720, 480, PixelFormat::YUV420P, 8) > sd);
+        assert_eq!(decode_memory(720, 480, PixelFormat::CUDA, 4), sd);
+    }
+
+    #[test]
+    fn decode_memory_from_coded_frames() {
+        let coded = |width, height, pixel_format: PixelFormat| {
+            let mut frame = *Frame::default();
+            frame.EncodedWidth = width;
+            frame.EncodedHeight = height;
+            frame.EncodedPixelFormat = pixel_format.to_pix_fmt();
+            Frame::create_frame(&frame)
+        };
+        let output = |width, height| OutputProperties {
+            width,
+            height,
+            pixel_format: PixelFormat::RGB24,
+        };
+        let sd = coded(720, 480, PixelFormat::YUV420P);
+        let uhd = coded(3840, 2160, PixelFormat::YUV420P);
+        let references = decode_memory(720, 480, PixelFormat::YUV420P, 4);
+
+        assert_eq!(source_decode_memory(&sd, None, 4), references);
+        assert!(
+            source_decode_memory(&uhd, None, 4)
+                > source_decode_memory(&sd, None, 4)
+        );
+        // Downscaling the output keeps the coded references
+        assert_eq!(
+            source_decode_memory(&uhd, Some(output(320, 180)), 4),
+            source_decode_memory(&uhd, None, 4) + 320 * 180 * 3
+        );
+        assert_eq!(
+            source_decode_memory(&sd, Some(output(1920, 1080)), 4),
+            references + 1920 * 1080 * 3
+        );
+    }
+
+    #[test]
+    fn first_decodable_frame() {
+        let clean = first_decodable(10, Ok).unwrap();
+        assert_eq!(clean, (0, 0));
+
+        let corrupt = |n| {
+            if n < 3 {
+                Err(Error::new(
+                    Errors::ERROR_DECODING,
+                    Errors::ERROR_CODEC,
+                    "Corrupt frame",
+                ))
+            } else {
+                Ok(n)
+            }
+        };
+        assert_eq!(first_decodable(10, corrupt).unwrap(), (3, 3));
+        assert!(first_decodable(3, corrupt).is_err());
+        assert!(first_decodable(0, Ok).is_err());
+    }
+
+    #[test]
+    fn cancelled_decode_stops_early() {
+        let cancel = AtomicBool::new(false);
+        let mut decoded = 0;
+        let result = collect_cancellable(10, &cancel, |n| {
+            decoded += 1;
+            if n == 2 {
+                cancel.store(true, Ordering::Relaxed);
+            }
+            Ok(n)
+        });
+
+        assert_eq!(decoded, 3);
+        assert_eq!(result.unwrap_err().sub_type(), Errors::ERROR_CANCELLED);
+
+        let cancel = AtomicBool::new(false);
+        assert_eq!(collect_cancellable(3, &cancel, Ok).unwrap(), [0, 1, 2]);
+    }
+
+    #[test]
+    fn valid_video_properties() {
+        let mut raw = *VideoProperties::default();
+        raw.NumFrames = 240;
+        assert_eq!(video_properties(&raw).NumFrames, 240);
+    }
+
+    #[test]
+    #[should_panic]
+    fn null_video_properties() {
+        video_properties(std::ptr::null());
+    }
+
+    #[test]
+    fn packed_rows_drop_padding() {
+        let data = [1, 2, 3, 0, 4, 5, 6, 0];
+        assert_eq!(packed_rows(&data, 4, 3), [1, 2, 3, 4, 5, 6]);
+        assert_eq!(packed_rows(&data, 8, 8), data);
+    }
+
+    #[test]
+    fn step_positions_every_third() {
+        assert!(step_positions(10, 3).eq([0, 3, 6, 9]));
+        assert!(step_positions(10, 1).eq(0..10));
+    }
+
+    #[test]
+    fn restore_after_success_and_error() {
+        let error =
+            |sub_type| Error::new(Errors::ERROR_DECODING, sub_type, "");
+
+        let mut calls = Vec::new();
+        let decoded = with_restore(
+            &mut calls,
+            |calls| {
+                calls.push("decode");
+                Ok(3)
+            },
+            |calls| {
+                calls.push("restore");
+                Ok(())
+            },
+        );
+        assert_eq!(decoded.unwrap(), 3);
+        assert_eq!(calls, ["decode", "restore"]);
+
+        let mut calls = Vec::new();
+        let failed: Result<(), Error> = with_restore(
+            &mut calls,
+            |calls| {
+                calls.push("decode");
+                Err(error(Errors::ERROR_CODEC))
+            },
+            |calls| {
+                calls.push("restore");
+                Err(error(Errors::ERROR_UNKNOWN))
+            },
+        );
+        assert_eq!(failed.unwrap_err().sub_type(), Errors::ERROR_CODEC);
+        assert_eq!(calls, ["decode", "restore"]);
+
+        let restore_failed = with_restore(
+            &mut (),
+            |_| Ok(3),
+            |_| Err(error(Errors::ERROR_UNKNOWN)),
+        );
+        assert_eq!(
+            restore_failed.unwrap_err().sub_type(),
+            Errors::ERROR_UNKNOWN
+        );
+    }
+
+    #[test]
+    fn restore_after_cancellation() {
+        let cancel = AtomicBool::new(false);
+        let mut restored = false;
+        let result = with_restore(
+            &mut restored,
+            |_| {
+                collect_cancellable(10, &cancel, |n| {
+                    cancel.store(true, Ordering::Relaxed);
+                    Ok(n)
+                })
+            },
+            |restored| {
+                *restored = true;
+                Ok(())
+            },
+        );
+
+        assert_eq!(result.unwrap_err().sub_type(), Errors::ERROR_CANCELLED);
+        assert!(restored);
+    }
+
+    #[test]
+    fn replay_output_format_after_reopening() {
+        let format = OutputFormat {
+            target_formats: vec![PixelFormat::RGB24.to_pix_fmt()],
+            width: 1280,
+            height: 720,
+            resizer: Resizers::RESIZER_LANCZOS,
+        };
+
+        let mut replayed = None;
+        replay_output_format(
+            Some(format.clone()),
+            |format| {
+                replayed = Some(format);
+                Ok(())
+            },
+            || panic!("An output format was set"),
+        )
+        .unwrap();
+        assert_eq!(replayed, Some(format));
+
+        let mut reset = false;
+        replay_output_format(
+            None,
+            |_| panic!("No output format was set"),
+            || reset = true,
+        )
+        .unwrap();
+        assert!(reset);
+    }
+
+    #[test]
+    fn negotiated_format_is_preferred() {
+        let prefs = [PixelFormat::YUV444P10LE, PixelFormat::RGB24];
+        for format in prefs {
+            assert_eq!(
+                selected_format(&prefs, format.to_pix_fmt()).unwrap(),
+                format
+            );
+        }
+
+        let fallback = PixelFormat::YUV420P.to_pix_fmt();
+        assert_eq!(
+            selected_format(&prefs, fallback).unwrap_err().sub_type(),
+            Errors::ERROR_UNSUPPORTED
+        );
+        assert!(selected_format(&[], fallback).is_err());
+    }
+
+    #[test]
+    fn single_target_output() {
+        let rgb24 = PixelFormat::RGB24.to_pix_fmt();
+        let yuv420p = PixelFormat::YUV420P.to_pix_fmt();
+        assert_eq!(
+            output_for(&[rgb24], 640, 480),
+            Some(OutputProperties {
+                width: 640,
+                height: 480,
+                pixel_format: PixelFormat::RGB24,
+            })
+        );
+        assert_eq!(output_for(&[rgb24, yuv420p], 640, 480), None);
+        assert_eq!(output_for(&[], 640, 480), None);
+    }
+
+    #[test]
+    fn source_must_be_a_file() {
+        let dir = std::env::temp_dir();
+        let error = check_source_file(&dir).unwrap_err();
+        assert_eq!(error.error_type(), Errors::ERROR_FILE_READ);
+        assert_eq!(error.sub_type(), Errors::ERROR_NO_FILE);
+
+        let missing = dir.join("ffms2-missing-source.mkv");
+        assert!(check_source_file(&missing).is_err());
+
+        let file = dir.join(format!("ffms2-source-{}", std::process::id()));
+        std::fs::write(&file, b"RIFF").unwrap();
+        let result = check_source_file(&file);
+        std::fs::remove_file(&file).unwrap();
+        assert!(result.is_ok());
+    }
+
+    #[test]
+    fn first_video_after_audio() {
+        use TrackType::*;
+
+        let tracks = [(TYPE_AUDIO, 900), (TYPE_VIDEO, 240), (TYPE_VIDEO, 24)];
+        assert_eq!(first_video_track(tracks).unwrap(), (1, 240));
+
+        // Video tracks which were not indexed have no frames
+        let tracks = [(TYPE_VIDEO, 0), (TYPE_AUDIO, 900), (TYPE_VIDEO, 1)];
+        assert_eq!(first_video_track(tracks).unwrap(), (2, 1));
+
+        let audio_only = [(TYPE_AUDIO, 900), (TYPE_SUBTITLE, 0)];
+        assert_eq!(
+            first_video_track(audio_only).unwrap_err().sub_type(),
+            Errors::ERROR_NOT_AVAILABLE
+        );
+    }
+
+    #[test]
+    fn image_seek_mode() {
+        assert_eq!(default_seek_mode(1), SeekMode::SEEK_LINEAR_NO_RW);
+        assert_eq!(default_seek_mode(240), SeekMode::SEEK_NORMAL);
+        assert_eq!(default_seek_mode(0), SeekMode::SEEK_NORMAL);
+    }
+
+    #[test]
+    fn frame_cursor_in_order() {
+        let mut cursor = FrameCursor::new(Vec::new(), 3);
+        let decode = |decoded: &mut Vec<usize>, n| {
+            decoded.push(n);
+            n
+        };
+
+        assert_eq!(cursor.positions.size_hint(), (3, Some(3)));
+        let frames: Vec<usize> =
+            std::iter::from_fn(|| cursor.decode_next(decode)).collect();
+        assert_eq!(frames, [0, 1, 2]);
+        assert_eq!(cursor.decode_next(decode), None);
+        assert_eq!(cursor.source, [0, 1, 2]);
+        assert_eq!(cursor.positions.size_hint(), (0, Some(0)));
+    }
+
+    #[test]
+    fn prefetch_one_frame_ahead() {
+        let decode = |decoded: &mut Vec<usize>, n| {
+            decoded.push(n);
+            n
+        };
+        let mut prefetcher = Prefetcher::new(Vec::new(), 3, decode);
+        assert_eq!(prefetcher.cursor.source, [0]);
+
+        assert_eq!(prefetcher.next(decode), Some(0));
+        assert_eq!(prefetcher.cursor.source, [0, 1]);
+        assert_eq!(prefetcher.next(decode), Some(1));
+        assert_eq!(prefetcher.next(decode), Some(2));
+        assert_eq!(prefetcher.next(decode), None);
+        assert_eq!(prefetcher.cursor.source, [0, 1, 2]);
+
+        let mut empty = Prefetcher::new(Vec::new(), 0, decode);
+        assert_eq!(empty.next(decode), None);
+        assert!(empty.cursor.source.is_empty());
+    }
+
+    #[test]
+    #[should_panic]
+    fn step_positions_zero() {
+        let _ = step_positions(10, 0);
+    }
+
+    fn properties(fps: (i32, i32), sar: (i32, i32)) -> VideoProperties {
+        let mut video_properties = *VideoProperties::default();
+        video_properties.FPSNumerator = fps.0;
+        video_properties.FPSDenominator = fps.1;
+        video_properties.SARNum = sar.0;
+        video_properties.SARDen = sar.1;
+        VideoProperties { video_properties }
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn video_properties_json_round_trip() {
+        let mut pal = properties((25, 1), (64, 45));
+        pal.NumFrames = 240;
+        pal.Rotation = 90;
+        pal.MasteringDisplayPrimariesX = [0.68, 0.265, 0.15];
+
+        let json = serde_json::to_string(&pal).unwrap();
+        let read: VideoProperties = serde_json::from_str(&json).unwrap();
+        assert_eq!(serde_json::to_string(&read).unwrap(), json);
+        assert_eq!(read.NumFrames, 240);
+        assert_eq!(read.MasteringDisplayPrimariesX, [0.68, 0.265, 0.15]);
+
+        let stereo =
+            serde_json::to_string(&Stereo3DType::S3D_TYPE_2D).unwrap();
+        assert_eq!(stereo, "\"S3D_TYPE_2D\"");
+    }
+
+    #[test]
+    fn color_range_round_trip() {
+        for range in [
+            ColorRanges::CR_UNSPECIFIED,
+            ColorRanges::CR_MPEG,
+            ColorRanges::CR_JPEG,
+        ] {
+            assert_eq!(
+                ColorRanges::new(range.to_color_ranges() as i32),
+                range
+            );
+        }
+        assert_eq!(ColorRanges::new(42), ColorRanges::CR_UNSPECIFIED);
+    }
+
+    #[test]
+    fn aspect_ratio_strings() {
+        let square = properties((25, 1), (1, 1));
+        assert_eq!(square.aspect_ratio_string(1920, 1080), "16:9");
+        assert_eq!(square.aspect_ratio_string(640, 480), "4:3");
+        assert_eq!(square.aspect_ratio_string(1000, 999), "1000:999");
+
+        let unknown = properties((25, 1), (0, 0));
+        assert_eq!(unknown.aspect_ratio_string(1280, 720), "16:9");
+
+        // 720x576 PAL 16:9
+        let pal = properties((25, 1), (64, 45));
+        assert_eq!(pal.aspect_ratio_string(720, 576), "16:9");
+    }
+
+    #[test]
+    fn rotated_anamorphic_display_size() {
+        // 720x576 PAL 16:9 with 8 pixels of crop on each side, rotated
+        let mut pal = properties((25, 1), (64, 45));
+        pal.CropLeft = 8;
+        pal.CropRight = 8;
+        pal.Rotation = 90;
+        assert_eq!(pal.corrected_display_size(720, 576), (576, 1001));
+
+        pal.Rotation = 180;
+        assert_eq!(pal.corrected_display_size(720, 576), (1001, 576));
+
+        let square = properties((25, 1), (0, 0));
+        assert_eq!(square.corrected_display_size(1920, 1080), (1920, 1080));
+    }
+
+    #[test]
+    fn display_matrix_ffms2_flips() {
+        // FFMS2 flips horizontally for 1 and vertically for -1
+        assert_eq!(DisplayMatrix::new(0, 1).0, [[-1, 0], [0, 1]]);
+        assert_eq!(DisplayMatrix::new(0, -1).0, [[1, 0], [0, -1]]);
+        assert_eq!(DisplayMatrix::new(0, 0).0, [[1, 0], [0, 1]]);
+        // The flip comes first, (1, 0) is mirrored to (-1, 0) then rotated
+        assert_eq!(DisplayMatrix::new(90, 1).0, [[0, -1], [-1, 0]]);
+        assert_eq!(
+            DisplayMatrix::new(0, 1).to_degrees_and_flip(),
+            (180, true)
+        );
+    }
+
+    #[test]
+    fn display_matrix_degrees_and_flip() {
+        assert_eq!(
+            DisplayMatrix([[1, 0], [0, 1]]).to_degrees_and_flip(),
+            (0, false)
+        );
+        assert_eq!(
+            DisplayMatrix([[0, -1], [1, 0]]).to_degrees_and_flip(),
+            (90, false)
+        );
+        assert_eq!(
+            DisplayMatrix([[0, 1], [-1, 0]]).to_degrees_and_flip(),
+            (270, false)
+        );
+        assert_eq!(
+            DisplayMatrix([[1, 0], [0, -1]]).to_degrees_and_flip(),
+            (0, true)
+        );
+        assert_eq!(
+            DisplayMatrix([[-1, 0], [0, 1]]).to_degrees_and_flip(),
+            (180, true)
+        );
+
+        for rotation in [0, 90, 180, 270] {
+            for flip in [false, true] {
+                let matrix = DisplayMatrix::new(rotation, -(flip as i32));
+                assert_eq!(matrix.to_degrees_and_flip(), (rotation, flip));
+            }
+        }
+        assert_eq!(
+            DisplayMatrix::new(-90, 0).to_degrees_and_flip(),
+            (270, false)
+        );
+    }
+
+    #[test]
+    fn compatible_properties() {
+        let hd = (1920, 1080);
+        let first = properties((30000, 1001), (1, 1));
+        let same_rate = properties((60000, 2002), (0, 0));
+        assert!(first.is_compatible_with(hd, &same_rate, hd));
+        assert!(!first.is_compatible_with(hd, &same_rate, (1280, 720)));
+        assert!(!first.is_compatible_with(hd, &same_rate, (1080, 1920)));
+        assert!(!first.is_compatible_with(
+            hd,
+            &properties((25, 1), (1, 1)),
+            hd
+        ));
+        assert!(!first.is_compatible_with(
+            hd,
+            &properties((30000, 1001), (4, 3)),
+            hd
+        ));
+
+        let mut cropped = properties((30000, 1001), (1, 1));
+        cropped.CropRight = 8;
+        assert!(!first.is_compatible_with(hd, &cropped, hd));
+    }
+}